@@ -1,118 +1,95 @@
-use crate::funcs::{assign_func_command, format_funcs};
-
-use super::lib::model::{
-    errors::ErrorContext, functions::Function, variables::Variable, EvaluationContext,
+use super::lib::{
+    model::{
+        errors::{ErrorContext, Frame},
+        functions::Function,
+        span::Span,
+        variables::Variable,
+    },
+    utils::{self, split_assignment},
+    Outcome, Session,
 };
-use super::lib::utils;
+
+use std::iter;
 
 use colored::Colorize;
-use rustmatheval::model::errors::InnerFunction;
+use itertools::Itertools;
 use utils::Pos;
 
-use super::error::{ContextualLibError, Error, LibError};
+use super::error::{ContextualLibError, Error};
 
-use super::vars::{assign_var, assign_var_command, format_vars};
+use super::vars::{format_assignment, format_vars};
 
-use super::lib::doeval;
-
-use super::funcs::format_func_name;
+use super::funcs::{format_func, format_func_name, format_funcs};
 
 use super::stringify::stringify;
 
 /// Interprets a given user `input` and executes the given command or evaluates the given expression.
 /// * `input` - The user submitted string to be interpreted
-/// * `vars` - The vector of `Variables` the user has already entered / will add to
-pub fn handle_input<'a>(
-    input: &str,
-    vars: &'a mut Vec<Variable>,
-    funcs: &'a mut Vec<Function>,
-) -> Result<String, Error> {
+/// * `session` - The session holding the user's variables and functions
+pub fn handle_input(input: &str, session: &mut Session) -> Result<String, Error> {
     if input.len() == 1 {
         if Variable::is(input) {
             // Variable list command
-            return if vars.is_empty() {
+            return if session.vars().is_empty() {
                 Ok("No vars".to_string())
             } else {
-                Ok(format_vars(vars))
+                Ok(format_vars(session.vars()))
             };
         } else if Function::is(input) {
-            return if funcs.is_empty() {
+            return if session.funcs().is_empty() {
                 Ok("No funcs".to_string())
             } else {
-                Ok(format_funcs(funcs, vars))
+                Ok(format_funcs(session))
             };
         }
     }
 
-    if input.contains('=') {
-        if Variable::is(input) {
-            // Assign / Reassign variable command
-            assign_var_command(input, vars, funcs)
-        } else if Function::is(input) {
-            assign_func_command(input, funcs, vars)
-        } else {
-            Err(Error::Assignment)
+    Ok(match session.execute(input)? {
+        Outcome::Value(value) => {
+            // The input evaluated succesfully, so it will tokenize too
+            let formatted = session
+                .tokenize(input)
+                .map_or_else(|_| input.to_string(), |tokens| stringify(&tokens));
+            format!("[ {} ] => {}", formatted, format!("{:.3}", value).blue())
         }
-    } else {
-        // Evaluate as normal
-        let context = EvaluationContext {
-            vars,
-            funcs,
-            depth: 0,
-            context: ErrorContext::Main,
-        };
-        let result = doeval(input, context);
-
-        let (x, repr) = result?;
-
-        let formatted = stringify(&repr);
-        let eval_string = format!("[ {} ] => {}", formatted, format!("{:.3}", x).blue());
-
-        let ans = Variable {
-            repr: "ans".to_string(),
-            value: x,
-        };
-
-        assign_var(ans, vars); // Set ans to new value
-
-        Ok(eval_string)
-    }
+        Outcome::Assignment(var) => {
+            let (_, right) = split_assignment(input).expect("Assignments contain an `=`");
+            format_assignment(&var, right, session)
+        }
+        Outcome::Definition(func) => format_func(&func, session),
+    })
 }
 
-/// Makes a highlighted error message pointing to a particular index
+/// Highlights the span of the input that an error points to, beneath its message
 /// * `msg` - The message to print
 /// * `input_str` - The erroneous input
-/// * `idx` - The location of the error
+/// * `span` - The location of the error
 ///
 /// Returns the formatted error string
-///
-/// ## Panics
-/// Panics if `idx > input_str.chars().count()`
-fn make_highlighted_error(msg: &str, input_str: &str, idx: usize) -> String {
-    let first = if idx > 0 {
-        utils::slice(input_str, 0, &Pos::Idx(idx))
-    } else {
-        "".to_string()
-    };
+fn make_highlighted_error(msg: &str, input_str: &str, span: Span) -> String {
+    // Empty spans, e.g. at the end of the input, still highlight a single character
+    let start = span.start;
+    let end = span.end.max(start + 1);
+
+    // Pad the input so that spans past its end highlight a space
+    let len = input_str.chars().count().max(end);
+    let padded = format!("{:<width$}", input_str, width = len);
+
     format!(
-        "{} at index [{}]\n{}{}{}\n{}{}",
+        "{}\n{}{}{}\n{}{}",
         msg,
-        idx.to_string().red(),
-        first,
-        input_str
-            .chars()
-            .nth(idx)
-            .unwrap()
-            .to_string()
+        utils::slice(&padded, 0, &Pos::Idx(start)),
+        utils::slice(&padded, start, &Pos::Idx(end))
             .on_red()
             .white(),
-        utils::slice(input_str, idx + 1, &Pos::End),
-        "~".repeat(idx).red().bold(),
-        "^".red()
+        utils::slice(&padded, end, &Pos::End),
+        "~".repeat(start).red().bold(),
+        "^".repeat(end - start).red()
     )
 }
 
-/// Produce an error message for a given [`super::lib::ContextualError`] and input string
+/// Produce an error message for a given [`super::lib::ContextualError`] and input string.
+/// The message comes from the library, located errors are highlighted in their source.
 /// * `error` - The error
 /// * `input` - The user's input
 pub fn handle_library_errors(contextual_error: &ContextualLibError, input: &str) -> String {
@@ -123,47 +100,59 @@ pub fn handle_library_errors(contextual_error: &ContextualLibError, input: &str)
         ErrorContext::Main => input,
         ErrorContext::Scoped(func) => &func.code,
     };
-    let msg = match error {
-        LibError::Parsing(idx) => make_highlighted_error("Couldn't parse the token", code, *idx),
-        LibError::Operand(op) => {
-            let msg = match op {
-                InnerFunction::Builtin(kind) => format!(
-                    "Operator [{}] requires an operand",
-                    format!("{:?}", kind).green()
-                ),
-                InnerFunction::User(f) => format!(
-                    "Function {} requires [{}] arguments",
-                    format_func_name(&f.name),
-                    format!("{}", f.args.len()).red()
-                ),
-            };
-            format!("Couldn't evaluate. {}.", msg)
-        }
-        LibError::EmptyStack => "Couldn't evalutate. Stack was empty?".to_string(),
-        LibError::MismatchingParens => "Couldn't evaluate. Mismatched parens.".to_string(),
-        LibError::UnknownVariable(idx) => make_highlighted_error("Unknown variable", code, *idx),
-        LibError::UnknownFunction(idx) => make_highlighted_error("Unknown function", code, *idx),
-        LibError::RecursionLimit => "Exceeded recursion limit.".to_string(),
+    let msg = if error.is_located() {
+        make_highlighted_error(&error.to_string(), code, error.span())
+    } else {
+        error.to_string()
     };
-    if let ErrorContext::Scoped(func) = context {
+    let msg = if let ErrorContext::Scoped(func) = context {
         format!("In function {}: {}", format_func_name(&func.name), msg)
     } else {
         msg
+    };
+    if contextual_error.trace.is_empty() {
+        msg
+    } else {
+        format!("{}\n{}", format_trace(&contextual_error.trace), msg)
     }
 }
 
+/// Lists the calls leading up to an error, outermost first, along with where each call was made
+/// * `trace` - The calls, innermost first
+fn format_trace(trace: &[Frame]) -> String {
+    let calls = trace.iter().enumerate().rev().map(|(idx, frame)| {
+        let caller = trace.get(idx + 1).map_or_else(String::new, |caller| {
+            format!(" in {}", format_func_name(&caller.name))
+        });
+        format!(
+            "  {}({}) at index [{}]{}",
+            format_func_name(&frame.name),
+            frame.args.iter().join(", "),
+            frame.span.start.to_string().red(),
+            caller
+        )
+    });
+    iter::once("Traceback, most recent call last:".to_string())
+        .chain(calls)
+        .join("\n")
+}
+
 /// Produces an error message to show to the user
 /// * `error` - The `Error`
 /// * `input` - The user's input
 ///
 /// Returns a formatted error string
-///
-/// ## Panics
-/// Does not handle `Error::Io`
 pub fn handle_errors(error: &Error, input: &str) -> String {
     match error {
-        Error::Assignment => "Couldn't assign. Malformed assignment statement.".to_string(),
         Error::Library(contextual_error) => handle_library_errors(contextual_error, input),
-        Error::Io(..) => unreachable!(),
+        Error::Io(inner) => format!("Couldn't access the file: {}", inner),
+        Error::UnknownCommand(name) => {
+            format!("Unknown command [{}], see {}", name.red(), ":help".cyan())
+        }
+        Error::Usage(usage) => format!("Usage: {}", usage.cyan()),
+        Error::Undefined(name) => format!("[{}] isn't defined", name.red()),
+        Error::UnknownName(name) => {
+            format!("No operator or constant is named [{}]", name.red())
+        }
     }
 }
@@ -0,0 +1,258 @@
+use std::{fs, path::Path};
+
+use colored::Colorize;
+use itertools::Itertools;
+
+use super::error::Error;
+use super::funcs::{format_func, format_func_name};
+use super::lib::{
+    model::{
+        constants::Constant,
+        functions::Function,
+        number::Number,
+        operators::{Arity, Associativity, Operator, FUNCTIONAL_STYLE_OPERATORS},
+        variables::Variable,
+    },
+    Session,
+};
+use super::rcfile;
+use super::vars::{format_var_name, format_vars};
+
+/// Commands start with this, to set them apart from expressions
+const PREFIX: char = ':';
+
+/// The name, usage, and description of each command, for `:help`
+const COMMANDS: &[(&str, &str, &str)] = &[
+    (
+        "help",
+        ":help [name]",
+        "List the commands, or describe an operator, constant, variable, or function",
+    ),
+    ("del", ":del $x | #f", "Delete a variable or function"),
+    (
+        "clear",
+        ":clear vars | funcs | all",
+        "Delete every variable, every function, or both",
+    ),
+    (
+        "save",
+        ":save <file>",
+        "Write the variables and functions to a file, in the same format as the RCFile",
+    ),
+    ("load", ":load <file>", "Execute every statement in a file"),
+    (
+        "reset",
+        ":reset",
+        "Delete every variable and function, then load the RCFile again",
+    ),
+    ("quit", ":quit", "Exit Rustcalc"),
+];
+
+/// A command to the REPL itself rather than an expression, e.g. `:help`
+pub enum Command<'a> {
+    Help(Option<&'a str>),
+    Delete(&'a str),
+    Clear(Clear),
+    Save(&'a str),
+    Load(&'a str),
+    Reset,
+    Quit,
+}
+
+/// What `:clear` deletes
+pub enum Clear {
+    Vars,
+    Funcs,
+    All,
+}
+
+/// Interpret `input` as a command, if it is one
+///
+/// ## Returns
+/// `None` if `input` isn't a command, otherwise the command or an error if it's unknown or malformed
+pub fn parse(input: &str) -> Option<Result<Command<'_>, Error>> {
+    let input = input.strip_prefix(PREFIX)?;
+    let (name, arg) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+    let arg = Some(arg.trim()).filter(|arg| !arg.is_empty());
+
+    // Aliases share the usage of the command they stand for
+    let command = match name {
+        "h" => "help",
+        "delete" => "del",
+        "q" | "exit" => "quit",
+        name => name,
+    };
+    let Some(&(_, usage, _)) = COMMANDS.iter().find(|(name, ..)| *name == command) else {
+        return Some(Err(Error::UnknownCommand(format!("{}{}", PREFIX, name))));
+    };
+
+    Some(match (command, arg) {
+        ("help", arg) => Ok(Command::Help(arg)),
+        ("del", Some(arg)) => Ok(Command::Delete(arg)),
+        ("clear", Some("vars")) => Ok(Command::Clear(Clear::Vars)),
+        ("clear", Some("funcs")) => Ok(Command::Clear(Clear::Funcs)),
+        ("clear", Some("all")) => Ok(Command::Clear(Clear::All)),
+        ("save", Some(arg)) => Ok(Command::Save(arg)),
+        ("load", Some(arg)) => Ok(Command::Load(arg)),
+        ("reset", None) => Ok(Command::Reset),
+        ("quit", None) => Ok(Command::Quit),
+        _ => Err(Error::Usage(usage)),
+    })
+}
+
+/// Execute a command, other than `Command::Quit`, which is up to the REPL
+///
+/// ## Returns
+/// A message to show to the user, or an error
+pub fn execute(command: Command, session: &mut Session) -> Result<String, Error> {
+    match command {
+        Command::Help(None) => Ok(COMMANDS
+            .iter()
+            .map(|(_, usage, description)| format!("{:<28}{}", usage.cyan(), description))
+            .join("\n")),
+        Command::Help(Some(name)) => help(name, session),
+        Command::Delete(name) => delete(name, session),
+        Command::Clear(target) => {
+            let (vars, funcs) = (session.vars().len(), session.funcs().len());
+            Ok(match target {
+                Clear::Vars => {
+                    session.clear_vars();
+                    format!("Deleted [{}] variables", vars)
+                }
+                Clear::Funcs => {
+                    session.clear_funcs();
+                    format!("Deleted [{}] functions", funcs)
+                }
+                Clear::All => {
+                    session.clear_vars();
+                    session.clear_funcs();
+                    format!("Deleted [{}] variables and [{}] functions", vars, funcs)
+                }
+            })
+        }
+        Command::Save(path) => {
+            fs::write(path, rcfile::format_session(session))?;
+            Ok(format!(
+                "Saved [{}] variables and [{}] functions to [{}]",
+                session.vars().len(),
+                session.funcs().len(),
+                path
+            ))
+        }
+        Command::Load(path) => {
+            let errors = rcfile::execute(Path::new(path), session)?;
+            let errors = errors.into_iter().map(|(n, message)| {
+                format!(
+                    "Error in [{}] on line [{}]: {}",
+                    path,
+                    format!("{}", n).red(),
+                    message
+                )
+            });
+            Ok(std::iter::once(format!("Loaded [{}]", path))
+                .chain(errors)
+                .join("\n"))
+        }
+        Command::Reset => {
+            session.clear_vars();
+            session.clear_funcs();
+            rcfile::load(session)?;
+            Ok("Reset the session".to_string())
+        }
+        Command::Quit => unreachable!("Quitting is handled by the REPL"),
+    }
+}
+
+/// Delete the variable or function `name`.
+/// Bare names are variables if there is one by that name, otherwise functions
+fn delete(name: &str, session: &mut Session) -> Result<String, Error> {
+    let deleted = if let Some(name) = name.strip_prefix('$') {
+        session
+            .remove_var(name)
+            .map(|var| format_var_name(&var.repr))
+    } else if let Some(name) = name.strip_prefix('#') {
+        session
+            .remove_func(name)
+            .map(|func| format_func_name(&func.name))
+    } else {
+        session
+            .remove_var(name)
+            .map(|var| format_var_name(&var.repr))
+            .or_else(|| {
+                session
+                    .remove_func(name)
+                    .map(|func| format_func_name(&func.name))
+            })
+    };
+    deleted
+        .map(|name| format!("Deleted {}", name))
+        .ok_or_else(|| Error::Undefined(name.to_string()))
+}
+
+/// Describe the operator, constant, variable, or function `name`
+fn help(name: &str, session: &Session) -> Result<String, Error> {
+    if Variable::is(name) {
+        return session
+            .vars()
+            .iter()
+            .find(|var| var.repr == name[1..])
+            .map(|var| format_vars(std::slice::from_ref(var)))
+            .ok_or_else(|| Error::Undefined(name.to_string()));
+    }
+    if Function::is(name) {
+        return session
+            .func(&name[1..])
+            .map(|func| format_func(func, session))
+            .ok_or_else(|| Error::Undefined(name.to_string()));
+    }
+
+    // Some names, like `-`, are shared by more than one operator
+    let operators = Operator::all()
+        .iter()
+        .filter(|op| op.repr.contains(&name))
+        .map(describe_operator);
+    let constants = Constant::all()
+        .iter()
+        .filter(|c| c.repr.contains(&name))
+        .map(|c| {
+            format!(
+                "[ {} ] => {}",
+                c.repr.iter().join(", ").yellow(),
+                format!("{:.3}", Number::complex(c.value)).blue()
+            )
+        });
+    let descriptions = operators.chain(constants).join("\n");
+    if descriptions.is_empty() {
+        Err(Error::UnknownName(name.to_string()))
+    } else {
+        Ok(descriptions)
+    }
+}
+
+/// Describe how an operator is written, e.g. `[ gcd ] gcd(a, b, ...)`
+fn describe_operator(op: &Operator) -> String {
+    let name = op.repr[0];
+    let args = match op.arity {
+        Arity::Fixed(n) => ["a", "b", "c"][..n].join(", "),
+        Arity::Variadic => "a, b, ...".to_string(),
+    };
+    let usage = if FUNCTIONAL_STYLE_OPERATORS.contains(&op.kind) {
+        format!("{}({})", name, args)
+    } else {
+        match (op.arity, op.associativity) {
+            (Arity::Fixed(2), _) => format!("a {} b", name),
+            // Factorial is the only postfix operator
+            (Arity::Fixed(1), Associativity::Left) => format!("a{}", name),
+            (Arity::Fixed(1), _) if name.chars().all(char::is_alphabetic) => {
+                format!("{} a", name)
+            }
+            _ => format!("{}a", name),
+        }
+    };
+    format!(
+        "[ {} ] {}, with precedence [{}]",
+        op.repr.iter().join(", ").green().bold(),
+        usage,
+        op.precedence
+    )
+}
@@ -10,6 +10,22 @@ lazy_static! {
         dir.push("rustcalc.rc");
         dir
     });
+    pub static ref SESSION_FILE: Option<PathBuf> = dirs::config_dir().map(|mut dir| {
+        dir.push("rustcalc-session.rc");
+        dir
+    });
 }
 
 pub const DEFAULT_RCFILE: &str = include_str!("../../res/rustcalc.rc");
+
+/// Evaluate using exact rational arithmetic
+pub const RATIONAL_FLAG: &str = "--rational";
+
+/// Resolve bare identifiers as variables and functions, without `$` and `#`
+pub const BARE_FLAG: &str = "--bare";
+
+/// Report division by zero, domain errors, and overflow instead of producing `NaN` or infinity
+pub const STRICT_FLAG: &str = "--strict";
+
+/// Don't restore the variables and functions from the previous session
+pub const FRESH_FLAG: &str = "--fresh";
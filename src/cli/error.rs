@@ -1,13 +1,20 @@
 #![allow(clippy::module_name_repetitions)]
 
-pub use super::lib::model::errors::{ContextualError as ContextualLibError, Error as LibError};
+pub use super::lib::model::errors::ContextualError as ContextualLibError;
 pub use std::io::Error as IoError;
 
 /// Error type for errors stemming from cli code, which includes `Errors` thrown by the library
 pub enum Error {
-    Assignment,
     Io(IoError),
     Library(ContextualLibError),
+    /// A command that doesn't exist, e.g. `:foo`
+    UnknownCommand(String),
+    /// A command with missing or malformed arguments, along with how it should be used
+    Usage(&'static str),
+    /// A variable or function that doesn't exist, e.g. `:del $foo`
+    Undefined(String),
+    /// `:help` for a name that isn't an operator, constant, variable, or function
+    UnknownName(String),
 }
 
 impl From<ContextualLibError> for Error {
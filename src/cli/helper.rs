@@ -0,0 +1,350 @@
+use std::{borrow::Cow, collections::HashSet, time::Duration};
+
+use colored::Colorize;
+use itertools::Itertools;
+use rustyline::{
+    completion::{Candidate, Completer, Pair},
+    highlight::Highlighter,
+    hint::{Hint, Hinter},
+    validate::{ValidationContext, ValidationResult, Validator},
+    Context, Helper,
+};
+
+use super::lib::{
+    doeval,
+    model::{
+        constants::Constant,
+        errors::Error,
+        functions::{Function, Functions},
+        number::Number,
+        operators::{Operator, OperatorType},
+        span::Span,
+        symbols::SymbolTable,
+        tokens::Token,
+        variables::{Scope, Variable},
+        EvaluationContext, IdentifierMode,
+    },
+    tokenize_partial,
+    utils::split_assignment,
+    Session,
+};
+use super::stringify::color_source;
+
+/// How long the preview of an expression's value may take to evaluate, so that typing stays responsive
+const HINT_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Operators whose results differ between evaluations, so they can't be previewed
+const RANDOM_OPERATORS: &[OperatorType] = &[OperatorType::RandomInt, OperatorType::RandomFloat];
+
+/// Line editing support for the REPL.
+/// It owns the session so that completions always reflect the current variables and functions
+pub struct CalcHelper {
+    pub session: Session,
+}
+
+impl CalcHelper {
+    pub const fn new(session: Session) -> Self {
+        Self { session }
+    }
+
+    /// Candidates for the session's variables, each name preceded by `sigil`
+    fn vars(&self, sigil: &'static str) -> impl Iterator<Item = Pair> + '_ {
+        self.session.vars().iter().map(move |var| Pair {
+            display: format!("{}{}", sigil, var.repr),
+            replacement: format!("{}{}", sigil, var.repr),
+        })
+    }
+
+    /// Candidates for the session's functions, each name preceded by `sigil`.
+    /// Functions are displayed with their arguments, e.g. `#f(a, b)`
+    fn funcs(&self, sigil: &'static str) -> impl Iterator<Item = Pair> + '_ {
+        self.session.funcs().iter().map(move |func| Pair {
+            display: format!("{}{}({})", sigil, func.name, func.args.join(", ")),
+            replacement: format!("{}{}", sigil, func.name),
+        })
+    }
+
+    /// Candidates for completing the name of a builtin, variable, or function that starts with `prefix`
+    /// * `prefix` - The start of the name, including its `$` or `#` for variables and functions
+    fn candidates(&self, prefix: &str) -> Vec<Pair> {
+        let candidates: Vec<Pair> = if Variable::is(prefix) {
+            self.vars("$").collect()
+        } else if Function::is(prefix) {
+            self.funcs("#").collect()
+        } else {
+            let builtins = Operator::all()
+                .iter()
+                .flat_map(|op| op.repr)
+                .chain(Constant::all().iter().flat_map(|c| c.repr))
+                .filter(|repr| repr.chars().all(is_name_char))
+                .map(|repr| Pair {
+                    display: (*repr).to_string(),
+                    replacement: (*repr).to_string(),
+                });
+            // Bare identifiers are written without their sigils
+            if self.session.options.identifiers == IdentifierMode::Bare {
+                builtins
+                    .chain(self.vars(""))
+                    .chain(self.funcs(""))
+                    .collect()
+            } else {
+                builtins.collect()
+            }
+        };
+
+        candidates
+            .into_iter()
+            .filter(|pair| pair.replacement.starts_with(prefix) && pair.replacement != prefix)
+            .sorted_by(|a, b| a.display().cmp(b.display()))
+            .dedup_by(|a, b| a.replacement == b.replacement)
+            .collect()
+    }
+}
+
+impl CalcHelper {
+    /// Color a statement as it's being typed.
+    /// The first character that can't be tokenized is marked, everything after it is left as is
+    fn highlight_statement(&self, line: &str) -> String {
+        let Some((left, right)) = split_assignment(line) else {
+            return highlight_expression(line, &self.session.context());
+        };
+
+        // The arguments of a function definition are available in its code
+        let args: SymbolTable<Variable> = definition_args(left)
+            .into_iter()
+            .map(|arg| Variable {
+                repr: arg.to_string(),
+                value: Number::Float(0.0),
+            })
+            .collect();
+        let session = self.session.context();
+        let context = EvaluationContext {
+            vars: Scope::with_parent(&args, &session.vars),
+            ..session.clone()
+        };
+        format!(
+            "{}{}{}",
+            highlight_assignee(left),
+            "=".cyan(),
+            highlight_expression(right, &context)
+        )
+    }
+}
+
+impl CalcHelper {
+    /// The value `line` would evaluate to, e.g. ` = 42.000`.
+    /// Returns `None` for assignments, expressions that produce errors, and those that use random operators
+    fn preview(&self, line: &str) -> Option<String> {
+        if line.trim().is_empty() || split_assignment(line).is_some() {
+            return None;
+        }
+
+        let mut context = self.session.context();
+        let limits = &mut context.options.limits;
+        limits.timeout = Some(limits.timeout.map_or(HINT_TIMEOUT, |t| t.min(HINT_TIMEOUT)));
+        if is_random(line, &context, &mut HashSet::new()) {
+            return None;
+        }
+
+        let (value, _) = doeval(line, context).ok()?;
+        Some(format!(" = {:.3}", value))
+    }
+}
+
+/// Determines if `expr` uses a random operator, either directly or through a user function
+/// * `seen` - The functions that have already been checked
+fn is_random(expr: &str, context: &EvaluationContext, seen: &mut HashSet<String>) -> bool {
+    let (tokens, _) = tokenize_partial(expr, context);
+    tokens.iter().any(|token| match token.inner {
+        Token::Operator {
+            inner: Functions::Builtin(op),
+        } => RANDOM_OPERATORS.contains(&op.kind),
+        Token::Operator {
+            inner: Functions::User(func),
+        } if seen.insert(func.name.clone()) => {
+            // The values of the arguments don't matter, only that they exist
+            let args = vec![Number::Float(0.0); func.arity()];
+            let args = func.create_variables(&args);
+            let context = EvaluationContext {
+                vars: Scope::with_parent(&args, &context.vars),
+                ..context.clone()
+            };
+            is_random(&func.code, &context, seen)
+        }
+        _ => false,
+    })
+}
+
+/// The names of the arguments on the left side of a function definition, `#f $a $b` or `f(a, b)`
+fn definition_args(left: &str) -> Vec<&str> {
+    let left = left.trim();
+    if let Some(definition) = left.strip_prefix('#') {
+        return definition
+            .split_whitespace()
+            .skip(1)
+            .map(|arg| arg.trim_start_matches('$'))
+            .collect();
+    }
+    left.split_once('(').map_or_else(Vec::new, |(_, args)| {
+        args.trim_end_matches(')')
+            .split(',')
+            .map(str::trim)
+            .filter(|arg| !arg.is_empty())
+            .collect()
+    })
+}
+
+/// Color the left side of an assignment, the sigiled names of variables and functions
+fn highlight_assignee(left: &str) -> String {
+    left.split_inclusive(char::is_whitespace)
+        .map(|word| {
+            if Variable::is(word) {
+                word.green().bold().to_string()
+            } else if Function::is(word) {
+                word.magenta().bold().to_string()
+            } else {
+                word.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Color an expression token by token, with the same colors as its evaluated form
+fn highlight_expression(expr: &str, context: &EvaluationContext) -> String {
+    let chars: Vec<char> = expr.chars().collect();
+    let (tokens, error) = tokenize_partial(expr, context);
+
+    let mut out = String::new();
+    let mut idx = 0;
+    for token in &tokens {
+        // Whitespace between tokens
+        out.extend(&chars[idx..token.span.start]);
+        let source: String = chars[token.span.start..token.span.end].iter().collect();
+        out.push_str(&color_source(&source, token).to_string());
+        idx = token.span.end;
+    }
+
+    // A paren that is still open may be closed on the next line
+    let error = error.filter(|error| !is_unclosed(error, &chars));
+    if let Some(start) = error.map(|error| error.span().start) {
+        if let Some(c) = chars.get(start) {
+            out.extend(&chars[idx..start]);
+            out.push_str(&c.to_string().on_red().white().to_string());
+            idx = start + 1;
+        }
+    }
+    out.extend(&chars[idx..]);
+    out
+}
+
+/// Determines if `error` is a left paren that was never closed
+fn is_unclosed(error: &Error, chars: &[char]) -> bool {
+    matches!(error, Error::MismatchingParens(span) if chars.get(span.start) == Some(&'('))
+}
+
+/// Determines if `c` may appear in the name of a builtin, variable, or function
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+impl Completer for CalcHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        // The name being typed ends at the cursor, including its sigil if there is one
+        let before = &line[..pos];
+        let mut start = before
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| is_name_char(*c))
+            .last()
+            .map_or(pos, |(idx, _)| idx);
+        if before[..start].ends_with(['$', '#']) {
+            start -= 1;
+        }
+
+        let prefix = &before[start..];
+        if prefix.is_empty() {
+            return Ok((pos, Vec::new()));
+        }
+        Ok((start, self.candidates(prefix)))
+    }
+}
+
+/// A preview of the value of the input, which is shown but never inserted
+pub struct ResultHint(String);
+
+impl Hint for ResultHint {
+    fn display(&self) -> &str {
+        &self.0
+    }
+
+    fn completion(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl Hinter for CalcHelper {
+    type Hint = ResultHint;
+
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<ResultHint> {
+        // Hints are shown after the cursor, so only preview complete lines
+        if pos < line.len() {
+            return None;
+        }
+        self.preview(line).map(ResultHint)
+    }
+}
+
+impl Highlighter for CalcHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        // Commands aren't expressions, only their names are colored
+        if line.starts_with(':') {
+            let (name, rest) = line.split_at(line.find(char::is_whitespace).unwrap_or(line.len()));
+            return Cow::Owned(format!("{}{}", name.cyan(), rest));
+        }
+        Cow::Owned(self.highlight_statement(line))
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(hint.dimmed().to_string())
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
+        // Any character may change how the rest of the line tokenizes
+        true
+    }
+}
+
+/// Input with unclosed parens continues on the next line, input with unmatched right parens can't be submitted
+impl Validator for CalcHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        let mut open = 0_usize;
+        for (idx, c) in ctx.input().chars().enumerate() {
+            match c {
+                '(' => open += 1,
+                ')' if open == 0 => {
+                    let error = Error::MismatchingParens(Span::new(idx, idx + 1));
+                    return Ok(ValidationResult::Invalid(Some(format!(
+                        "\n{}",
+                        error.to_string().red()
+                    ))));
+                }
+                ')' => open -= 1,
+                _ => {}
+            }
+        }
+        Ok(if open > 0 {
+            ValidationResult::Incomplete
+        } else {
+            ValidationResult::Valid(None)
+        })
+    }
+}
+
+impl Helper for CalcHelper {}
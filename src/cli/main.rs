@@ -1,21 +1,34 @@
 #![warn(clippy::pedantic, clippy::nursery)]
+#![allow(
+    clippy::uninlined_format_args,
+    clippy::result_large_err,
+    clippy::large_enum_variant,
+    clippy::non_std_lazy_statics
+)]
 
 mod cli;
+mod commands;
 mod config;
 mod error;
 mod funcs;
+mod helper;
 mod rcfile;
 mod stringify;
-mod utils;
 mod vars;
 
-use lib::{doeval, model::EvaluationContext};
+use lib::{
+    doeval,
+    model::{number::NumericMode, EvaluationContext, IdentifierMode, Options},
+    Session,
+};
 pub use rustmatheval as lib;
 
-use config::HISTORY_FILE;
+use config::{BARE_FLAG, FRESH_FLAG, HISTORY_FILE, RATIONAL_FLAG, STRICT_FLAG};
 use rustyline::Editor;
 
+use commands::Command;
 use error::Error;
+use helper::CalcHelper;
 use std::{env, process};
 
 use cli::{handle_errors, handle_input};
@@ -23,18 +36,34 @@ use cli::{handle_errors, handle_input};
 use crate::cli::handle_library_errors;
 
 pub fn main() -> ! {
-    // One-shot mode
-    let args = env::args();
-
     // Usually the first argument is the path to the executable
-    // So if there's more than one argument we interpret that as a one-shot
-    if args.len() > 1 {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    // Flags come before the expression
+    let mut options = Options::default();
+    let mut fresh = false;
+    while let Some(flag) = args.first() {
+        match flag.as_str() {
+            RATIONAL_FLAG => options.numeric = NumericMode::Rational,
+            BARE_FLAG => options.identifiers = IdentifierMode::Bare,
+            STRICT_FLAG => options.strict = true,
+            FRESH_FLAG => fresh = true,
+            _ => break,
+        }
+        args.remove(0);
+    }
+
+    // One-shot mode
+    // If there are any remaining arguments we interpret them as a one-shot
+    if !args.is_empty() {
         // Combine all of the args into a string
-        let fold = |acc: String, x: String| format!("{} {}", acc, x);
-        let input: String = args.skip(1).fold(String::new(), fold);
+        let input = args.join(" ");
 
         // Evaluate
-        let context = EvaluationContext::default();
+        let context = EvaluationContext {
+            options,
+            ..EvaluationContext::default()
+        };
         let code = match doeval(&input, context) {
             Ok((result, _)) => {
                 println!("{:.3}", result);
@@ -51,34 +80,36 @@ pub fn main() -> ! {
         process::exit(code);
     }
 
-    let mut vars = vec![];
-    let mut funcs = vec![];
+    let mut session = Session::new(options);
 
-    if let Err(inner) = rcfile::load(&mut vars, &mut funcs) {
+    if let Err(inner) = rcfile::load(&mut session) {
         match inner {
             Error::Io(inner) => {
                 println!("Error loading RCFile: {:#?}", inner);
             }
             _ => unreachable!(),
         }
-    };
+    }
+
+    // The previous session is restored after the RCFile, so its values take precedence
+    if !fresh {
+        if let Err(Error::Io(inner)) = rcfile::restore(&mut session) {
+            println!("Error restoring session: {:#?}", inner);
+        }
+    }
 
-    let mut editor = Editor::<()>::new();
+    let mut editor = Editor::<CalcHelper>::new();
+    editor.set_helper(Some(CalcHelper::new(session)));
 
     if let Some(path) = HISTORY_FILE.as_deref() {
         editor.load_history(path).ok();
     }
 
     loop {
-        #[allow(clippy::single_match_else)]
+        #[allow(clippy::single_match_else, clippy::option_if_let_else)]
         let input = match editor.readline("> ") {
             Ok(line) => line.trim().to_string(),
-            Err(_) => {
-                if let Some(path) = HISTORY_FILE.as_deref() {
-                    editor.save_history(&path).ok();
-                }
-                process::exit(0)
-            }
+            Err(_) => quit(&mut editor),
         };
 
         if input.is_empty() {
@@ -88,7 +119,18 @@ pub fn main() -> ! {
         // Add the line to the history
         editor.add_history_entry(&input);
 
-        match handle_input(&input, &mut vars, &mut funcs) {
+        // The helper is set above
+        let Some(helper) = editor.helper_mut() else {
+            unreachable!()
+        };
+        let result = match commands::parse(&input) {
+            Some(Ok(Command::Quit)) => quit(&mut editor),
+            Some(command) => {
+                command.and_then(|command| commands::execute(command, &mut helper.session))
+            }
+            None => handle_input(&input, &mut helper.session),
+        };
+        match result {
             Ok(formatted) => println!("{}", formatted),
             Err(error) => {
                 let msg = handle_errors(&error, &input);
@@ -97,3 +139,14 @@ pub fn main() -> ! {
         }
     }
 }
+
+/// Save the history and session, then exit
+fn quit(editor: &mut Editor<CalcHelper>) -> ! {
+    if let Some(path) = HISTORY_FILE.as_deref() {
+        editor.save_history(path).ok();
+    }
+    if let Some(helper) = editor.helper() {
+        rcfile::persist(&helper.session).ok();
+    }
+    process::exit(0)
+}
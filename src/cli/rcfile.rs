@@ -1,8 +1,9 @@
-use super::config::{DEFAULT_RCFILE, RCFILE};
+use super::config::{DEFAULT_RCFILE, RCFILE, SESSION_FILE};
 use super::error::{Error, IoError};
-use super::lib::model::{functions::Function, variables::Variable};
+use super::lib::Session;
 use colored::Colorize;
-use std::{fs, io::ErrorKind::NotFound};
+use itertools::Itertools;
+use std::{fs, io::ErrorKind::NotFound, path::Path};
 
 use super::cli::{handle_errors, handle_input};
 
@@ -12,16 +13,13 @@ use super::cli::{handle_errors, handle_input};
 /// * May write to stdout
 ///
 /// ## Input
-/// * `vars` - A mutable reference to the applications variables. Executing the rcfile may create variables.
+/// * `session` - A mutable reference to the application's session. Executing the rcfile may create variables and functions.
 ///
 /// ## Output
 /// Returns an empty `Result` on success, or a `CliError` from io operations
-pub fn load(vars: &mut Vec<Variable>, funcs: &mut Vec<Function>) -> Result<(), Error> {
-    let path = match RCFILE.as_deref() {
-        Some(path) => path,
-        None => {
-            return Err(IoError::new(NotFound, "Couldn't get path for config directory").into())
-        }
+pub fn load(session: &mut Session) -> Result<(), Error> {
+    let Some(path) = RCFILE.as_deref() else {
+        return Err(IoError::new(NotFound, "Couldn't get path for config directory").into());
     };
 
     // If RCFile doesn't exist, create it and write the default contents
@@ -33,7 +31,53 @@ pub fn load(vars: &mut Vec<Variable>, funcs: &mut Vec<Function>) -> Result<(), E
         fs::write(path, DEFAULT_RCFILE)?;
     }
 
-    // Read
+    for (n, message) in execute(path, session)? {
+        println!(
+            "Error in RCFile on line [{}]: {}",
+            format!("{}", n).red(),
+            message
+        );
+    }
+    Ok(())
+}
+
+/// Restore the variables and functions of the previous session from `SESSION_FILE`, if there is one.
+/// Statements that fail are reported on stdout
+///
+/// ## Output
+/// Returns an empty `Result` on success, or a `CliError` from io operations
+pub fn restore(session: &mut Session) -> Result<(), Error> {
+    let Some(path) = SESSION_FILE.as_deref().filter(|path| path.exists()) else {
+        return Ok(());
+    };
+    for (n, message) in execute(path, session)? {
+        println!(
+            "Error in session file on line [{}]: {}",
+            format!("{}", n).red(),
+            message
+        );
+    }
+    Ok(())
+}
+
+/// Write the session's variables and functions to `SESSION_FILE`, to be restored on the next startup
+///
+/// ## Output
+/// Returns an empty `Result` on success, or a `CliError` from io operations
+pub fn persist(session: &Session) -> Result<(), Error> {
+    let Some(path) = SESSION_FILE.as_deref() else {
+        return Err(IoError::new(NotFound, "Couldn't get path for config directory").into());
+    };
+    fs::write(path, format_session(session))?;
+    Ok(())
+}
+
+/// Execute every statement in a file, skipping empty lines and `//` comments.
+/// Succesfully executing statements are silent
+///
+/// ## Output
+/// The line number and error message of each statement that failed, or a `CliError` if the file couldn't be read
+pub fn execute(path: &Path, session: &mut Session) -> Result<Vec<(usize, String)>, Error> {
     let lines = fs::read_to_string(path)?;
 
     // Filter out empty and comment lines
@@ -44,16 +88,31 @@ pub fn load(vars: &mut Vec<Variable>, funcs: &mut Vec<Function>) -> Result<(), E
         .filter(|(_, line)| !(line.is_empty() || line.starts_with("//")));
 
     // Feed each line through `handle_input` and make use of `handle_errors`
-    // Succesfully executing statements are silent
-    for (n, line) in lines {
-        if let Err(inner) = handle_input(line, vars, funcs) {
-            let message = handle_errors(&inner, line);
-            println!(
-                "Error in RCFile on line [{}]: {}",
-                format!("{}", n).red(),
-                message
-            );
+    Ok(lines
+        .filter_map(|(n, line)| {
+            handle_input(line, session)
+                .err()
+                .map(|inner| (n, handle_errors(&inner, line)))
+        })
+        .collect())
+}
+
+/// Write a session's variables and functions as statements that recreate them when executed, see [`execute`].
+/// Values that can't be written as an expression, like `NaN`, are commented out.
+/// Everything is written with sigils, so it can be restored with or without `--bare`
+pub fn format_session(session: &Session) -> String {
+    let vars = session.vars().iter().map(|var| {
+        let statement = format!("${} = {}", var.repr, var.value);
+        if var.value.is_finite() {
+            statement
+        } else {
+            format!("// {}", statement)
         }
-    }
-    Ok(())
+    });
+    let funcs = session.funcs().iter().map(|func| {
+        let args = func.args.iter().map(|arg| format!(" ${}", arg)).join("");
+        // Bare identifiers in the code get their sigils, to match the header
+        format!("#{}{} = {}", func.name, args, session.sigil_code(func))
+    });
+    vars.chain(funcs).map(|line| line + "\n").collect()
 }
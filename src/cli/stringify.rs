@@ -7,30 +7,37 @@ use crate::{funcs::format_func_name, vars::format_var_name};
 
 use super::lib::model::{
     operators::{Associativity, OperatorType},
+    span::Spanned,
     tokens::{ParenType, Token},
 };
 
 /// Creates a colored string representation of the input tokens
-pub fn stringify(tokens: &[Token]) -> String {
-    _stringify(tokens, color_cli)
+pub fn stringify(tokens: &[Spanned<Token>]) -> String {
+    let tokens: Vec<Token> = tokens.iter().map(|token| token.inner.clone()).collect();
+    stringify_with(&tokens, color_cli)
 }
 
 /// Construct the ideal representation of a `Token`
 fn ideal_repr(tok: &Token) -> String {
     match tok {
         Token::Number { value } => value.to_string(),
+        // Units follow their value without an explicit operator, e.g. `5 m`
+        Token::Operator {
+            inner: Functions::Builtin(inner),
+        } if inner.kind == OperatorType::UnitMul => String::new(),
         Token::Operator {
             inner: Functions::Builtin(inner),
         } => inner.repr[0].to_string(),
         Token::Operator {
             inner: Functions::User(inner),
-        } => inner.name.to_string(),
+        } => inner.name.clone(),
         Token::Paren { kind } => match kind {
             ParenType::Left => '('.to_string(),
             ParenType::Right => ')'.to_string(),
         },
         Token::Constant { inner } => inner.repr[0].to_string(),
-        Token::Variable { inner } => inner.repr.to_string(),
+        Token::Unit { inner } => inner.symbol(),
+        Token::Variable { inner } => inner.repr.clone(),
         Token::Comma => ",".to_string(),
     }
 }
@@ -51,29 +58,46 @@ fn color_cli(string: &str, token: &Token) -> ColoredString {
         },
         Token::Paren { .. } => string.red(),
         Token::Constant { .. } => string.yellow(),
+        Token::Unit { .. } => string.cyan(),
         Token::Variable { .. } => format_var_name(string),
     }
 }
 
+/// Color the source of a token as it was written, e.g. while it's being typed.
+/// Unlike [`color_cli`], sigils aren't added to the names of variables and functions
+pub fn color_source(string: &str, token: &Token) -> ColoredString {
+    match token {
+        Token::Operator {
+            inner: Functions::User(_),
+        } => string.magenta().bold(),
+        Token::Variable { .. } => string.green().bold(),
+        _ => color_cli(string, token),
+    }
+}
+
 /// Determine if a space should be come after `cur` in a string representation.
 #[allow(clippy::unnested_or_patterns)]
 fn spaces(cur: &Token) -> bool {
     // Cases:
-    // - Spaces after value types: numbers, variables, and constants
+    // - Spaces after value types: numbers, variables, constants, and units
     // - Spaces after r_parens and commas
     // - Spaces after all operators except function-style ones: sin, cos, tan, sqrt, ..
-    //   - and pow
+    //   - and pow, and the multiplication between a value and its unit
     // - Otherwise no spaces
     match cur {
         Token::Operator {
             inner: Functions::Builtin(op),
-        } => (!FUNCTIONAL_STYLE_OPERATORS.contains(&op.kind) && op.kind != OperatorType::Pow) as _,
+        } => {
+            !(FUNCTIONAL_STYLE_OPERATORS.contains(&op.kind)
+                || [OperatorType::Pow, OperatorType::UnitMul].contains(&op.kind))
+        }
         Token::Paren {
             kind: ParenType::Right,
         }
         | Token::Number { .. }
         | Token::Variable { .. }
         | Token::Constant { .. }
+        | Token::Unit { .. }
         | Token::Comma => true,
 
         // Otherwise none
@@ -104,7 +128,8 @@ fn exclude_space(next: &Token) -> bool {
     }
 }
 
-fn _stringify<F, T: Display>(tokens: &[Token], colorize: F) -> String
+#[allow(clippy::format_collect)]
+fn stringify_with<F, T: Display>(tokens: &[Token], colorize: F) -> String
 where
     F: Fn(&str, &Token) -> T,
 {
@@ -149,3 +174,35 @@ where
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+
+    #![allow(clippy::non_ascii_literal)]
+
+    use rustmatheval::{model::EvaluationContext, tokenize_and_transform};
+
+    use super::stringify_with;
+
+    fn plain(input: &str) -> String {
+        let tokens = tokenize_and_transform(input, &EvaluationContext::default()).unwrap();
+        let tokens: Vec<_> = tokens.into_iter().map(|token| token.inner).collect();
+        stringify_with(&tokens, |string, _| string.to_string())
+    }
+
+    #[test]
+    fn test_stringify_implicit_parens() {
+        for (input, expected) in &[
+            ("sin 5", "sin(5)"),
+            ("gamma 5", "Γ(5)"),
+            ("lgamma 5 - 1", "lgamma(5) - 1"),
+            ("erf 0.5 + 1", "erf(0.5) + 1"),
+            ("erfc 2", "erfc(2)"),
+            ("isprime 7", "isprime(7)"),
+            ("nextprime 7 + 1", "nextprime(7) + 1"),
+            ("factor 360", "factor(360)"),
+        ] {
+            assert_eq!(plain(input), *expected, "Checking [{}]", input);
+        }
+    }
+}
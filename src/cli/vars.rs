@@ -1,13 +1,8 @@
 use colored::{ColoredString, Colorize};
 use itertools::Itertools;
 
-use super::error::{ContextualLibError, Error, LibError};
-use super::lib::doeval;
-use super::lib::model::{
-    errors::ErrorContext, functions::Function, variables::Variable, EvaluationContext,
-};
+use super::lib::{model::variables::Variable, Session};
 use super::stringify::stringify;
-use super::utils::insert_or_swap_sort;
 
 pub fn format_var_name(name: &str) -> ColoredString {
     format!("${}", name.green().bold()).normal()
@@ -27,68 +22,21 @@ pub fn format_vars(vars: &[Variable]) -> String {
     vars.iter().map(format_var).join("\n")
 }
 
-/// Takes the given user `input` and splits it up into a name and value to be assigned or reassigned to a [Variable] in `vars`
-pub fn assign_var_command(
-    input: &str,
-    vars: &mut Vec<Variable>,
-    funcs: &[Function],
-) -> Result<String, Error> {
-    // Variable assignment / reassignment
+/// Formats the confirmation of a variable assignment
+/// * `var` - The variable that was assigned
+/// * `right` - The right side of the statement, the expression that was assigned
+/// * `session` - The session the variable was assigned in
+pub fn format_assignment(var: &Variable, right: &str, session: &Session) -> String {
+    // The expression evaluated succesfully, so it will tokenize too
+    let repr = session
+        .tokenize(right)
+        .map_or_else(|_| right.trim().to_string(), |tokens| stringify(&tokens));
 
-    let sides: Vec<&str> = input.split('=').collect();
-    let trimmed_left = sides[0].trim(); // Trim here to remove space between end of variable name and = sign
-
-    if sides.len() != 2 || sides[1].is_empty() {
-        // Multiple = signs or Assigning without using a $ prefix or no expression
-        return Err(Error::Assignment);
-    }
-
-    // Trim again to remove whitespace between end of variable name and = sign
-    let user_repr: String = trimmed_left[1..].to_string();
-
-    let context = EvaluationContext {
-        vars,
-        funcs,
-        depth: 0,
-        context: ErrorContext::Main,
-    };
-
-    // Get value for variable
-    let result = doeval(sides[1], context);
-    if let Err(ContextualLibError {
-        error: LibError::Parsing(idx),
-        ..
-    }) = result
-    {
-        return Err(Error::Library(
-            LibError::Parsing(idx + sides[0].len() + 1).with_context(ErrorContext::Main),
-        ));
-        // Length of untrimmed lefthand side
-        // Offset is added so that highlighting can be added to expressions that come after an '=' during assignment
-    }
-    let (user_value, repr) = result?;
-
-    let conf_string = format!(
+    format!(
         "[ ${} {} {} ] => {}",
-        user_repr.green().bold(),
+        var.repr.green().bold(),
         "=".cyan(),
-        stringify(&repr),
-        format!("{:.3}", user_value).blue()
-    );
-
-    let var = Variable {
-        repr: user_repr,
-        value: user_value,
-    };
-
-    assign_var(var, vars);
-
-    Ok(conf_string)
-}
-
-pub fn assign_var(var: Variable, vars: &mut Vec<Variable>) {
-    let repr = var.repr.clone();
-    let cmp = |v: &Variable| repr.cmp(&v.repr);
-
-    insert_or_swap_sort(vars, var, cmp);
+        repr,
+        format!("{:.3}", var.value).blue()
+    )
 }
@@ -0,0 +1,179 @@
+use crate::{
+    eval::eval,
+    model::{
+        errors::{ContextualError, Error},
+        expr::Expr,
+        number::Number,
+        span::Span,
+        EvaluationContext,
+    },
+    rpn::rpn,
+    tokenize_and_transform,
+};
+
+/// An expression that has been parsed into an expression tree ahead of time.
+/// It can be evaluated any number of times against different variables and functions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledExpression {
+    source: String,
+    expr: Expr,
+}
+
+impl CompiledExpression {
+    /// Compile a string containing a mathematical expression
+    ///
+    /// * `string` - The input to compile
+    /// * `context` - The evaluation context used to resolve variables and functions
+    ///
+    /// ## Errors
+    /// Reraises errors that occur during tokenization, rpn conversion, and tree construction
+    pub fn new(string: &str, context: &EvaluationContext) -> Result<Self, Error> {
+        let tokens = tokenize_and_transform(string, context)?;
+        let rpn = rpn(&tokens)?;
+        let expr = Expr::from_rpn(&rpn)?;
+
+        Ok(Self {
+            source: string.to_string(),
+            expr,
+        })
+    }
+
+    /// The source string this expression was compiled from
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The expression tree
+    pub const fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    /// Determines if every variable and function this expression references is available in `context`
+    fn is_bound(&self, context: &EvaluationContext) -> bool {
+        self.expr
+            .variables()
+            .iter()
+            .all(|name| context.vars.get(name).is_some())
+            && self
+                .expr
+                .functions()
+                .iter()
+                .all(|name| context.funcs.get(name).is_some())
+    }
+
+    /// Evaluate this expression
+    ///
+    /// * `context` - The evaluation context, which provides the current values of variables.
+    ///   At the top level, with a depth of zero, its budget is reset so that limits apply to each evaluation
+    ///
+    /// ## Returns
+    /// The result of the computation
+    ///
+    /// ## Errors
+    /// Returns an error if the expression couldn't be computed
+    pub fn eval(&self, context: EvaluationContext) -> Result<Number, ContextualError> {
+        if context.depth >= context.options.limits.max_depth {
+            let span = Span::new(0, self.source.chars().count());
+            return Err(Error::RecursionLimit(span).with_context(context.context));
+        }
+        if context.depth == 0 {
+            context.budget.reset();
+        }
+
+        if self.is_bound(&context) {
+            return eval(&self.expr, &context);
+        }
+
+        // Something this expression depends on has gone out of scope since it was compiled
+        // Compiling it again will either produce a descriptive error or a valid expression
+        match Self::new(&self.source, &context) {
+            Ok(compiled) => compiled.eval(context),
+            Err(error) => Err(error.with_context(context.context)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    #![allow(clippy::shadow_unrelated)]
+
+    use super::{CompiledExpression, Error, EvaluationContext};
+    use crate::model::{
+        errors::ErrorContext,
+        functions::Function,
+        span::Span,
+        symbols::SymbolTable,
+        variables::{Scope, Variable},
+    };
+
+    #[test]
+    fn test_eval_rebinds_variables() {
+        let vars = SymbolTable::from([Variable {
+            repr: "x".to_string(),
+            value: 2.0.into(),
+        }]);
+        let context = EvaluationContext {
+            vars: Scope::new(&vars),
+            ..EvaluationContext::default()
+        };
+        let compiled = CompiledExpression::new("$x^2 + 1", &context).unwrap();
+        assert_eq!(compiled.source(), "$x^2 + 1");
+        let result = compiled.eval(context).unwrap();
+        assert_same!(result.to_f64(), 5.0);
+
+        for value in &[0.0, 3.0, -4.0] {
+            let vars = SymbolTable::from([Variable {
+                repr: "x".to_string(),
+                value: (*value).into(),
+            }]);
+            let context = EvaluationContext {
+                vars: Scope::new(&vars),
+                ..EvaluationContext::default()
+            };
+            let result = compiled.eval(context).unwrap();
+            assert_same!(result.to_f64(), value * value + 1.0);
+        }
+    }
+
+    #[test]
+    fn test_eval_missing_variable() {
+        let vars = SymbolTable::from([Variable {
+            repr: "x".to_string(),
+            value: 2.0.into(),
+        }]);
+        let context = EvaluationContext {
+            vars: Scope::new(&vars),
+            ..EvaluationContext::default()
+        };
+        let compiled = CompiledExpression::new("1 + $x", &context).unwrap();
+
+        let error = compiled.eval(EvaluationContext::default()).unwrap_err();
+        assert_eq!(error.error, Error::UnknownVariable(Span::new(4, 6)));
+        assert_eq!(error.context, ErrorContext::Main);
+    }
+
+    #[test]
+    fn test_function_caches_code() {
+        let funcs = SymbolTable::from([Function::new(
+            "sq".to_string(),
+            vec!["a".to_string()],
+            "$a * $a".to_string(),
+        )]);
+        let context = EvaluationContext {
+            funcs: &funcs,
+            ..EvaluationContext::default()
+        };
+        let compiled = CompiledExpression::new("#sq(3) + #sq 4", &context).unwrap();
+        assert!(format!("{:?}", funcs[0]).contains("[compiled: false]"));
+
+        let result = compiled.eval(context.clone()).unwrap();
+        assert_same!(result.to_f64(), 25.0);
+        assert!(format!("{:?}", funcs[0]).contains("[compiled: true]"));
+        let result = funcs[0].apply(&[5.0.into()], &context).unwrap();
+        assert_same!(result.to_f64(), 25.0);
+
+        // The cache is not part of a function's identity
+        assert_eq!(funcs[0].clone(), funcs[0]);
+    }
+}
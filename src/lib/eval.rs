@@ -1,61 +1,107 @@
 use crate::model::EvaluationContext;
 
 use super::model::{
-    errors::{ContextualError, Error, InnerFunction},
-    functions::Functions,
-    tokens::Token,
+    errors::{ContextualError, Error, Frame, InnerFunction},
+    expr::{Callee, Expr},
+    number::Number,
+    operators::OperatorType,
+    units::Quantity,
 };
 
-/// Evaluate a list of tokens
-/// * `tokens` - The tokens
+/// Evaluate an expression tree
+/// * `expr` - The expression
+/// * `context` - The evaluation context. Every variable and function referenced by `expr` must be present.
 ///
-/// Returns the result as a 64-bit float or an `Error`
-pub fn eval(tokens: &[Token], context: EvaluationContext) -> Result<f64, ContextualError> {
-    // We need a mutable copy of the tokens
-    let mut stack: Vec<Token> = tokens.iter().rev().copied().collect();
-    let mut args: Vec<f64> = Vec::new();
-
-    while let Some(token) = stack.pop() {
-        match token {
-            Token::Number { value } => {
-                args.push(value);
+/// Returns the resulting `Number` or an `Error`
+pub fn eval(expr: &Expr, context: &EvaluationContext) -> Result<Number, ContextualError> {
+    // Every operation counts against the budget
+    if let Some(span) = expr.span() {
+        context
+            .budget
+            .step(&context.options.limits, span)
+            .map_err(|error| error.with_context(context.context.clone()))?;
+    }
+
+    Ok(match expr {
+        Expr::Literal(value) => Number::literal(value, context.options.numeric),
+        Expr::Constant(inner) => Number::complex(inner.value),
+        Expr::Unit(unit) => Quantity::unit(*unit),
+        Expr::Variable(name) => context
+            .vars
+            .get(name)
+            .expect("Variables are resolved before evaluation")
+            .value
+            .clone(),
+        Expr::Unary { op, operand, span } => op
+            .apply(&[eval(operand, context)?], context.options, &context.budget)
+            .map_err(|error| error.with_span(*span).with_context(context.context.clone()))?,
+        // `&&` and `||` short-circuit, the right side is only evaluated if it affects the result
+        Expr::Binary { op, lhs, rhs, .. }
+            if [OperatorType::And, OperatorType::Or].contains(&op.kind) =>
+        {
+            let lhs = eval(lhs, context)?;
+            if lhs.truthy() == (op.kind == OperatorType::Or) {
+                Number::from_bool(lhs.truthy(), lhs.is_exact())
+            } else {
+                let rhs = eval(rhs, context)?;
+                Number::from_bool(rhs.truthy(), lhs.is_exact() && rhs.is_exact())
             }
-            Token::Constant { inner } => {
-                args.push(inner.value);
+        }
+        Expr::Binary { op, lhs, rhs, span } => op
+            .apply(
+                &[eval(lhs, context)?, eval(rhs, context)?],
+                context.options,
+                &context.budget,
+            )
+            .map_err(|error| error.with_span(*span).with_context(context.context.clone()))?,
+        // Only the branch that is taken is evaluated
+        Expr::Call {
+            callee: Callee::Builtin(op),
+            args,
+            ..
+        } if op.kind == OperatorType::If => {
+            if eval(&args[0], context)?.truthy() {
+                eval(&args[1], context)?
+            } else {
+                eval(&args[2], context)?
             }
-            Token::Variable { inner } => args.push(inner.value),
-            Token::Operator { inner: op } => {
-                let start = if let Some(x) = args.len().checked_sub(op.arity()) {
-                    x
-                } else {
-                    let inner = match op {
-                        Functions::Builtin(b) => InnerFunction::Builtin(b.kind),
-                        Functions::User(func) => InnerFunction::User(func.clone()),
-                    };
-                    return Err(Error::Operand(inner).with_context(context.context));
-                };
-
-                // Takes the last `op.arity` number of values from `args`
-                // `start = args.len() - op.arity`
-                let args_: Vec<f64> = args.drain(start..).collect();
-
-                let result = match op {
-                    Functions::Builtin(b) => (b.doit)(&args_),
-                    Functions::User(f) => f.apply(&args_, &context)?,
-                };
-
-                // Push the result of the evaluation
-                stack.push(Token::Number { value: result });
+        }
+        Expr::Call { callee, args, span } => {
+            let args = args
+                .iter()
+                .map(|arg| eval(arg, context))
+                .collect::<Result<Vec<Number>, ContextualError>>()?;
+
+            match callee {
+                Callee::Builtin(op) => {
+                    op.apply(&args, context.options, &context.budget)
+                        .map_err(|error| {
+                            error.with_span(*span).with_context(context.context.clone())
+                        })?
+                }
+                Callee::User(name) => {
+                    let func = context
+                        .funcs
+                        .get(name)
+                        .expect("Functions are resolved before evaluation");
+
+                    // The function may have been redefined since this expression was built
+                    if func.arity() != args.len() {
+                        return Err(Error::Operand(InnerFunction::User(func.clone()), *span)
+                            .with_context(context.context.clone()));
+                    }
+
+                    func.apply(&args, context).map_err(|error| {
+                        error.in_call(Frame {
+                            name: name.clone(),
+                            args: args.clone(),
+                            span: *span,
+                        })
+                    })?
+                }
             }
-            Token::Paren { .. } | Token::Comma => {}
         }
-    }
-
-    // Result
-    if args.len() == 1 {
-        return Ok(args[0]);
-    }
-    Err(Error::EmptyStack.with_context(context.context))
+    })
 }
 
 #[cfg(test)]
@@ -68,22 +114,30 @@ mod tests {
     use crate::{
         model::{
             errors::ErrorContext,
+            expr::Expr,
             functions::{Function, Functions},
             operators::OperatorType,
-            tokens::ParenType,
-            variables::Variable,
+            span::Spanned,
+            symbols::SymbolTable,
+            tokens::{ParenType, Token},
+            variables::{Scope, Variable},
         },
         rpn::rpn,
     };
 
-    use super::{eval, EvaluationContext, Token};
+    use super::{eval, EvaluationContext};
+
+    fn tree(tokens: &[Token]) -> Expr {
+        let tokens: Vec<Spanned<Token>> = tokens.iter().cloned().map(Spanned::from).collect();
+        Expr::from_rpn(&rpn(&tokens).unwrap()).unwrap()
+    }
 
     #[test]
     fn test_eval_ok() {
-        let tokens = [Token::Number { value: 4.67 }];
+        let tokens = [Token::Number { value: 4.67.into() }];
 
-        let result = eval(&tokens, EvaluationContext::default()).unwrap();
-        assert_same!(result, 4.67);
+        let result = eval(&tree(&tokens), &EvaluationContext::default()).unwrap();
+        assert_same!(result.to_f64(), 4.67);
 
         // sin(5)^2 + cos(5)^2 => 1
         let tokens = [
@@ -91,56 +145,55 @@ mod tests {
             Token::Paren {
                 kind: ParenType::Left,
             },
-            Token::Number { value: 5.0 },
+            Token::Number { value: 5.0.into() },
             Token::Paren {
                 kind: ParenType::Right,
             },
             Token::operator(OperatorType::Pow),
-            Token::Number { value: 2.0 },
+            Token::Number { value: 2.0.into() },
             Token::operator(OperatorType::Add),
             Token::operator(OperatorType::Cos),
             Token::Paren {
                 kind: ParenType::Left,
             },
-            Token::Number { value: 5.0 },
+            Token::Number { value: 5.0.into() },
             Token::Paren {
                 kind: ParenType::Right,
             },
             Token::operator(OperatorType::Pow),
-            Token::Number { value: 2.0 },
+            Token::Number { value: 2.0.into() },
         ];
-        let tokens = rpn(&tokens).unwrap();
-        let result = eval(&tokens, EvaluationContext::default()).unwrap();
-        assert_same!(result, 1.0);
+        let result = eval(&tree(&tokens), &EvaluationContext::default()).unwrap();
+        assert_same!(result.to_f64(), 1.0);
     }
 
     #[test]
     fn test_eval_functions() {
-        let funcs = [Function {
-            name: "inv".to_string(),
-            args: vec!["x".to_string()],
-            code: "1/$x".to_string(),
-        }];
-        let vars = [Variable {
+        let funcs = SymbolTable::from([Function::new(
+            "inv".to_string(),
+            vec!["x".to_string()],
+            "1/$x".to_string(),
+        )]);
+        let vars = SymbolTable::from([Variable {
             repr: "e".to_string(),
-            value: 5.0,
-        }];
+            value: 5.0.into(),
+        }]);
         let context = EvaluationContext {
-            vars: &vars,
+            vars: Scope::new(&vars),
             funcs: &funcs,
             context: ErrorContext::Main,
             depth: 0,
+            ..EvaluationContext::default()
         };
 
         let tokens = [
             Token::Operator {
                 inner: Functions::User(&funcs[0]),
             },
-            Token::Number { value: 1.0 },
+            Token::Number { value: 1.0.into() },
         ];
-        let tokens = rpn(&tokens).unwrap();
-        let result = eval(&tokens, context.clone()).unwrap();
-        assert_same!(result, 1.0);
+        let result = eval(&tree(&tokens), &context).unwrap();
+        assert_same!(result.to_f64(), 1.0);
 
         let tokens = [
             Token::Operator {
@@ -148,9 +201,8 @@ mod tests {
             },
             Token::Variable { inner: &vars[0] },
         ];
-        let tokens = rpn(&tokens).unwrap();
-        let result = eval(&tokens, context.clone()).unwrap();
-        assert_same!(result, 1.0 / vars[0].value);
+        let result = eval(&tree(&tokens), &context).unwrap();
+        assert_same!(result.to_f64(), 1.0 / vars[0].value.to_f64());
 
         let tokens = [
             Token::Operator {
@@ -162,37 +214,37 @@ mod tests {
             Token::Operator {
                 inner: Functions::User(&funcs[0]),
             },
-            Token::Number { value: 8.0 },
+            Token::Number { value: 8.0.into() },
         ];
-        let tokens = rpn(&tokens).unwrap();
-        let result = eval(&tokens, context.clone()).unwrap();
-        assert_same!(result, 1.0 / 8.0);
-
-        let funcs = [Function {
-            name: "ident".to_string(),
-            args: vec!["a".to_string()],
-            code: "$a".to_string(),
-        }];
+        let result = eval(&tree(&tokens), &context).unwrap();
+        assert_same!(result.to_f64(), 1.0 / 8.0);
+
+        let funcs = SymbolTable::from([Function::new(
+            "ident".to_string(),
+            vec!["a".to_string()],
+            "$a".to_string(),
+        )]);
         let context = EvaluationContext {
-            vars: &[],
             funcs: &funcs,
             context: ErrorContext::Main,
             depth: 0,
+            ..EvaluationContext::default()
         };
 
         let tokens = [
             Token::Operator {
                 inner: Functions::User(&funcs[0]),
             },
-            Token::Number { value: 1.0 },
+            Token::Number { value: 1.0.into() },
             Token::operator(OperatorType::Add),
             Token::Operator {
                 inner: Functions::User(&funcs[0]),
             },
-            Token::Number { value: -1.0 },
+            Token::Number {
+                value: (-1.0).into(),
+            },
         ];
-        let tokens = rpn(&tokens).unwrap();
-        let result = eval(&tokens, context).unwrap();
-        assert_same!(result, 0.0);
+        let result = eval(&tree(&tokens), &context).unwrap();
+        assert_same!(result.to_f64(), 0.0);
     }
 }
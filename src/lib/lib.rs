@@ -1,29 +1,42 @@
 #![warn(clippy::pedantic, clippy::nursery)]
-#![allow(clippy::must_use_candidate, clippy::missing_panics_doc)]
+#![allow(
+    clippy::must_use_candidate,
+    clippy::missing_panics_doc,
+    clippy::result_large_err,
+    clippy::uninlined_format_args
+)]
 
 #[macro_use]
 pub mod utils;
 
+mod compile;
 mod eval;
 mod rpn;
+mod session;
 mod tokenize;
 mod transform;
 
 pub mod model;
 
+pub use compile::CompiledExpression;
 use eval::eval;
 use model::EvaluationContext;
 use rpn::rpn;
-pub use tokenize::tokenize;
+pub use session::{Outcome, Session, ANS};
+pub use tokenize::{tokenize, tokenize_partial};
 use transform::implicit_coeffs;
 
 use crate::transform::implicit_parens;
 
 use self::model::{
     errors::{ContextualError, Error},
+    expr::Expr,
+    number::Number,
+    span::{Span, Spanned},
     tokens::Token,
 };
 
+/// The default limit on how deeply user functions may be nested, see [`model::Limits`]
 pub const RECURSION_LIMIT: u8 = 25;
 
 /// Tokenize a string and perform transformations on it, e.g. adding implicit parentheses and coefficients
@@ -32,14 +45,14 @@ pub const RECURSION_LIMIT: u8 = 25;
 /// * `context` - The evaluation context
 ///
 /// ## Returns
-/// A transformed list of parsed tokens, or an error
+/// A transformed list of parsed tokens with their spans, or an error
 ///
 /// ## Errors
 /// Reraises errors that occur during tokenization
 pub fn tokenize_and_transform<'a>(
     string: &str,
     context: &EvaluationContext<'a>,
-) -> Result<Vec<Token<'a>>, Error> {
+) -> Result<Vec<Spanned<Token<'a>>>, Error> {
     let mut tokens = tokenize(string, context)?;
     implicit_parens(&mut tokens);
     implicit_coeffs(&mut tokens);
@@ -49,20 +62,23 @@ pub fn tokenize_and_transform<'a>(
 /// Evaluate a string containing a mathematical expression
 ///
 /// * `string` - The string
-/// * `vars` - The available `Variable`s.
-/// These must be sorted such that no variable's representation is a subset of one that comes after it
+/// * `context` - The evaluation context, which provides the available variables and functions
 ///
 /// ## Returns
-/// The result of the computation as an a 64-bit float plus the result of the tokenization
+/// The result of the computation plus the result of the tokenization
 ///
 /// ## Errors
 /// Returns an error if the expression couldn't be computed
 pub fn doeval<'a>(
     string: &str,
     context: EvaluationContext<'a>,
-) -> Result<(f64, Vec<Token<'a>>), ContextualError> {
-    if context.depth == RECURSION_LIMIT {
-        return Err(Error::RecursionLimit.with_context(context.context));
+) -> Result<(Number, Vec<Spanned<Token<'a>>>), ContextualError> {
+    if context.depth >= context.options.limits.max_depth {
+        let span = Span::new(0, string.chars().count());
+        return Err(Error::RecursionLimit(span).with_context(context.context));
+    }
+    if context.depth == 0 {
+        context.budget.reset();
     }
 
     let tokens = match tokenize_and_transform(string, &context) {
@@ -75,53 +91,72 @@ pub fn doeval<'a>(
         Err(error) => return Err(error.with_context(context.context)),
     };
 
-    let result = eval(&rpn, context)?;
+    let expr = match Expr::from_rpn(&rpn) {
+        Ok(expr) => expr,
+        Err(error) => return Err(error.with_context(context.context)),
+    };
+
+    let result = eval(&expr, &context)?;
     Ok((result, tokens))
 }
 
 #[cfg(test)]
 mod tests {
 
-    #![allow(clippy::shadow_unrelated, clippy::needless_for_each)]
+    #![allow(
+        clippy::shadow_unrelated,
+        clippy::needless_for_each,
+        clippy::iter_on_single_items
+    )]
 
     use super::doeval;
 
     use crate::{
         model::{
-            constants::Constant, constants::ConstantType, errors::ErrorContext,
-            operators::OperatorType, tokens::ParenType, variables::Variable, EvaluationContext,
+            constants::Constant,
+            constants::ConstantType,
+            errors::{ErrorContext, InnerFunction},
+            functions::Function,
+            number::{Number, NumericMode},
+            operators::OperatorType,
+            span::Span,
+            symbols::SymbolTable,
+            tokens::ParenType,
+            variables::{Scope, Variable},
+            EvaluationContext, IdentifierMode, Limits, Options,
         },
         Error, Token,
     };
+    use std::time::Duration;
 
     macro_rules! context {
         ($vars:ident) => {
             EvaluationContext {
-                vars: &$vars,
-                funcs: &[],
+                vars: Scope::new(&$vars),
                 depth: 0,
                 context: ErrorContext::Main,
-            };
+                ..EvaluationContext::default()
+            }
         };
     }
 
     #[test]
     #[allow(clippy::too_many_lines)]
     fn test_doeval_ok() {
-        let vars = [Variable {
+        let vars = SymbolTable::from([Variable {
             repr: "x".to_string(),
-            value: 5.5,
-        }];
+            value: 5.5.into(),
+        }]);
 
         // Relatively simple case with a variable
         let (result, tokens) = doeval("1.1 + 2.2 + $x", context!(vars)).unwrap();
-        assert_same!(result, 8.8);
+        assert_same!(result.to_f64(), 8.8);
         assert_eq!(
             tokens,
             [
-                Token::Number { value: 1.1 },
+                Token::Number { value: 1.1.into() },
                 Token::operator(OperatorType::Add),
-                Token::Number { value: 2.2 },
+                Token::Number { value: 2.2.into() },
                 Token::operator(OperatorType::Add),
                 Token::Variable { inner: &vars[0] }
             ]
@@ -129,7 +164,7 @@ mod tests {
 
         // Functions w/o parens
         let (result, tokens) = doeval("sin pi", context!(vars)).unwrap();
-        assert_same!(result, std::f64::consts::PI.sin());
+        assert_same!(result.to_f64(), std::f64::consts::PI.sin());
         assert_eq!(
             tokens,
             [
@@ -147,22 +182,22 @@ mod tests {
         );
 
         let (result, tokens) = doeval("1 plus 7 sub 2 times 3", context!(vars)).unwrap();
-        assert_same!(result, 2.0);
+        assert_same!(result.to_f64(), 2.0);
         assert_eq!(
             tokens,
             [
-                Token::Number { value: 1.0 },
+                Token::Number { value: 1.0.into() },
                 Token::operator(OperatorType::Add,),
-                Token::Number { value: 7.0 },
+                Token::Number { value: 7.0.into() },
                 Token::operator(OperatorType::Sub,),
-                Token::Number { value: 2.0 },
+                Token::Number { value: 2.0.into() },
                 Token::operator(OperatorType::Mul,),
-                Token::Number { value: 3.0 },
+                Token::Number { value: 3.0.into() },
             ]
         );
 
         let (result, tokens) = doeval("sin(1 + 2 + 3)", context!(vars)).unwrap();
-        assert_same!(result, ((1.0 + 2.0 + 3.0) as f64).sin());
+        assert_same!(result.to_f64(), (1.0_f64 + 2.0 + 3.0).sin());
         assert_eq!(
             tokens,
             [
@@ -170,11 +205,11 @@ mod tests {
                 Token::Paren {
                     kind: ParenType::Left,
                 },
-                Token::Number { value: 1.0 },
+                Token::Number { value: 1.0.into() },
                 Token::operator(OperatorType::Add,),
-                Token::Number { value: 2.0 },
+                Token::Number { value: 2.0.into() },
                 Token::operator(OperatorType::Add,),
-                Token::Number { value: 3.0 },
+                Token::Number { value: 3.0.into() },
                 Token::Paren {
                     kind: ParenType::Right,
                 },
@@ -182,14 +217,14 @@ mod tests {
         );
 
         let (result, tokens) = doeval("(1)", context!(vars)).unwrap();
-        assert_same!(result, 1.0);
+        assert_same!(result.to_f64(), 1.0);
         assert_eq!(
             tokens,
             [
                 Token::Paren {
                     kind: ParenType::Left,
                 },
-                Token::Number { value: 1.0 },
+                Token::Number { value: 1.0.into() },
                 Token::Paren {
                     kind: ParenType::Right,
                 },
@@ -197,7 +232,7 @@ mod tests {
         );
 
         let (result, tokens) = doeval("((1))", context!(vars)).unwrap();
-        assert_same!(result, 1.0);
+        assert_same!(result.to_f64(), 1.0);
         assert_eq!(
             tokens,
             [
@@ -207,7 +242,7 @@ mod tests {
                 Token::Paren {
                     kind: ParenType::Left,
                 },
-                Token::Number { value: 1.0 },
+                Token::Number { value: 1.0.into() },
                 Token::Paren {
                     kind: ParenType::Right,
                 },
@@ -218,29 +253,29 @@ mod tests {
         );
 
         let (result, tokens) = doeval("-1", context!(vars)).unwrap();
-        assert_same!(result, -1.0);
+        assert_same!(result.to_f64(), -1.0);
         assert_eq!(
             tokens,
             [
                 Token::operator(OperatorType::Negative,),
-                Token::Number { value: 1.0 },
+                Token::Number { value: 1.0.into() },
             ]
         );
 
         let (result, tokens) = doeval("1 + -1", context!(vars)).unwrap();
-        assert_same!(result, 0.0);
+        assert_same!(result.to_f64(), 0.0);
         assert_eq!(
             tokens,
             [
-                Token::Number { value: 1.0 },
+                Token::Number { value: 1.0.into() },
                 Token::operator(OperatorType::Add,),
                 Token::operator(OperatorType::Negative,),
-                Token::Number { value: 1.0 },
+                Token::Number { value: 1.0.into() },
             ]
         );
 
         let (result, tokens) = doeval("-   (  1.1 +  2.2)", context!(vars)).unwrap();
-        assert_same!(result, -3.3);
+        assert_same!(result.to_f64(), -3.3);
         assert_eq!(
             tokens,
             [
@@ -248,9 +283,9 @@ mod tests {
                 Token::Paren {
                     kind: ParenType::Left,
                 },
-                Token::Number { value: 1.1 },
+                Token::Number { value: 1.1.into() },
                 Token::operator(OperatorType::Add,),
-                Token::Number { value: 2.2 },
+                Token::Number { value: 2.2.into() },
                 Token::Paren {
                     kind: ParenType::Right,
                 },
@@ -258,13 +293,327 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_doeval_operator_families() {
+        [
+            ("ln e", 1.0),
+            ("log 1000", 3.0),
+            ("log2(8)", 3.0),
+            ("exp 0", 1.0),
+            ("asin 1", std::f64::consts::FRAC_PI_2),
+            ("atan2(1, 1)", std::f64::consts::FRAC_PI_4),
+            ("sinh 0 + cosh 0 + tanh 0", 1.0),
+            ("abs -3 + floor 2.7 + ceil 2.1", 8.0),
+            ("round 2.5 + trunc -2.7", 1.0),
+            ("sign(-4)", -1.0),
+            ("cbrt 27", 3.0),
+            ("hypot(3, 4)", 5.0),
+            ("gamma 5 + erf 0 + erfc 0", 25.0),
+            ("Γ(0.5)^2", std::f64::consts::PI),
+            ("0.5! / gamma(1.5)", 1.0),
+            ("nCr(5, 2) + nPr(5, 2)", 30.0),
+            ("isprime 97 + isprime 91", 1.0),
+            ("nextprime(100)", 101.0),
+            ("7 // 2 + -7 // 2", -1.0),
+            ("-7 % 3 + -7 %% 3 + 7 floormod -3", -1.0),
+            ("-7 euclidmod 3 + 7 euclidmod -3", 3.0),
+            ("7.5 // 2 + 7.5 %% 2", 4.5),
+            ("gcd(6.0, 4)", 2.0),
+        ]
+        .iter()
+        .for_each(|(input, expected)| {
+            let (result, _) = doeval(input, EvaluationContext::default()).unwrap();
+            assert_same!(
+                result.to_f64(),
+                *expected,
+                "Checking evaluation of [{}]",
+                input
+            );
+        });
+    }
+
+    #[test]
+    fn test_doeval_variadic() {
+        [
+            ("max(1 + 2, 3)", 3.0),
+            ("max(1, 5, 3) * 2", 10.0),
+            ("min(4, -1, 7)", -1.0),
+            ("max 5", 5.0),
+            ("sum(1, 2, 3, 4)", 10.0),
+            ("mean(1, 2, 3, 4)", 2.5),
+            ("median(5, 1, 3)", 3.0),
+            ("product(2, 3, 4)", 24.0),
+            ("gcd(12, 18, 27)", 3.0),
+            ("lcm(4, 6, 10)", 60.0),
+            ("max(1, max(2, 3), sum(4, 5))", 9.0),
+        ]
+        .iter()
+        .for_each(|(input, expected)| {
+            let (result, _) = doeval(input, EvaluationContext::default()).unwrap();
+            assert_same!(
+                result.to_f64(),
+                *expected,
+                "Checking evaluation of [{}]",
+                input
+            );
+        });
+
+        let error = doeval("max()", EvaluationContext::default()).unwrap_err();
+        assert_eq!(
+            error.error,
+            Error::Operand(InnerFunction::Builtin(OperatorType::Max), Span::new(0, 3))
+        );
+    }
+
+    #[test]
+    fn test_doeval_conditionals() {
+        [
+            ("1 < 2", 1.0),
+            ("2 < 1", 0.0),
+            ("2 <= 2 && 3 >= 4", 0.0),
+            ("1 + 1 == 2", 1.0),
+            ("1 != 1 || 5 > 4", 1.0),
+            ("not 0", 1.0),
+            ("not 2 > 1", 0.0),
+            ("1 + not 0", 2.0),
+            ("2 * not 0", 2.0),
+            ("2 ^ not 1", 1.0),
+            ("1 + not 0 && 0", 0.0),
+            ("1 + not not 5", 2.0),
+            ("if(1 > 0, 10, 20)", 10.0),
+            ("if(0, 10, 20) + 1", 21.0),
+        ]
+        .iter()
+        .for_each(|(input, expected)| {
+            let (result, _) = doeval(input, EvaluationContext::default()).unwrap();
+            assert_same!(
+                result.to_f64(),
+                *expected,
+                "Checking evaluation of [{}]",
+                input
+            );
+        });
+    }
+
+    #[test]
+    fn test_doeval_lazy_conditionals() {
+        // Without laziness, these would never terminate
+        let funcs = SymbolTable::from([
+            Function::new(
+                "fact".to_string(),
+                vec!["n".to_string()],
+                "if($n <= 1, 1, $n * #fact($n - 1))".to_string(),
+            ),
+            Function::new(
+                "forever".to_string(),
+                vec!["n".to_string()],
+                "#forever($n)".to_string(),
+            ),
+        ]);
+        let context = EvaluationContext {
+            funcs: &funcs,
+            ..EvaluationContext::default()
+        };
+
+        let (result, _) = doeval("#fact(10)", context.clone()).unwrap();
+        assert_same!(result.to_f64(), 3_628_800.0);
+        let (result, _) = doeval("if(1, 2, #forever(1))", context.clone()).unwrap();
+        assert_same!(result.to_f64(), 2.0);
+        let (result, _) = doeval("0 && #forever(1)", context.clone()).unwrap();
+        assert_same!(result.to_f64(), 0.0);
+        let (result, _) = doeval("1 || #forever(1)", context.clone()).unwrap();
+        assert_same!(result.to_f64(), 1.0);
+
+        let error = doeval("if(0, 2, #forever(1))", context).unwrap_err();
+        assert!(matches!(error.error, Error::RecursionLimit(_)));
+    }
+
+    #[test]
+    fn test_doeval_limits() {
+        let funcs = SymbolTable::from([Function::new(
+            "fib".to_string(),
+            vec!["n".to_string()],
+            "if($n < 2, $n, #fib($n - 1) + #fib($n - 2))".to_string(),
+        )]);
+        let with_limits = |limits| EvaluationContext {
+            funcs: &funcs,
+            options: Options {
+                limits,
+                ..Options::default()
+            },
+            ..EvaluationContext::default()
+        };
+
+        let (result, _) = doeval("#fib(10)", with_limits(Limits::default())).unwrap();
+        assert_same!(result.to_f64(), 55.0);
+
+        let limits = Limits {
+            max_depth: 5,
+            ..Limits::default()
+        };
+        let error = doeval("#fib(10)", with_limits(limits)).unwrap_err();
+        assert!(matches!(error.error, Error::RecursionLimit(_)));
+
+        // The work of every call counts toward the same budget
+        let limits = Limits {
+            max_steps: Some(1000),
+            ..Limits::default()
+        };
+        let context = with_limits(limits);
+        let error = doeval("#fib(20)", context.clone()).unwrap_err();
+        assert!(matches!(error.error, Error::StepLimit(_)));
+        assert_eq!(context.budget.steps(), 1001);
+
+        // Each evaluation starts with a fresh budget, even when the context is reused
+        let (result, _) = doeval("#fib(5)", context.clone()).unwrap();
+        assert_same!(result.to_f64(), 5.0);
+        assert!(context.budget.steps() < 1000);
+
+        let limits = Limits {
+            timeout: Some(Duration::ZERO),
+            ..Limits::default()
+        };
+        let error = doeval("1 + #fib(20)", with_limits(limits)).unwrap_err();
+        assert_eq!(error.error, Error::Timeout(Span::new(2, 3)));
+
+        let limits = Limits {
+            timeout: Some(Duration::from_millis(20)),
+            ..Limits::default()
+        };
+        let context = with_limits(limits);
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(doeval("1 + 1", context.clone()).is_ok());
+
+        // Long-running operators are stopped too
+        let error = doeval("nextprime(2^1023)", context).unwrap_err();
+        assert_eq!(error.error, Error::Timeout(Span::new(0, 9)));
+    }
+
+    #[test]
+    fn test_doeval_strict() {
+        let strict = EvaluationContext {
+            options: Options {
+                strict: true,
+                ..Options::default()
+            },
+            ..EvaluationContext::default()
+        };
+
+        for (input, expected) in &[
+            ("1 / 0", Error::DivisionByZero(Span::new(2, 3))),
+            ("1.5 / 0", Error::DivisionByZero(Span::new(4, 5))),
+            ("5 % 0", Error::DivisionByZero(Span::new(2, 3))),
+            ("5 // 0", Error::DivisionByZero(Span::new(2, 4))),
+            ("5 %% 0", Error::DivisionByZero(Span::new(2, 4))),
+            (
+                "nCr(-1, 2)",
+                Error::Domain(OperatorType::Choose, Span::new(0, 3)),
+            ),
+            ("0^-1", Error::DivisionByZero(Span::new(1, 2))),
+            (
+                "sqrt(-4)",
+                Error::Domain(OperatorType::Sqrt, Span::new(0, 4)),
+            ),
+            (
+                "ln(0 - 1)",
+                Error::Domain(OperatorType::Ln, Span::new(0, 2)),
+            ),
+            (
+                "(-1)!",
+                Error::Domain(OperatorType::Factorial, Span::new(4, 5)),
+            ),
+            ("ln(0)", Error::Domain(OperatorType::Ln, Span::new(0, 2))),
+            (
+                "log(0)",
+                Error::Domain(OperatorType::Log10, Span::new(0, 3)),
+            ),
+            (
+                "log2(0)",
+                Error::Domain(OperatorType::Log2, Span::new(0, 4)),
+            ),
+            (
+                "lgamma(0 - 2)",
+                Error::Domain(OperatorType::Lgamma, Span::new(0, 6)),
+            ),
+            (
+                "exp(1000)",
+                Error::Overflow(OperatorType::Exp, Span::new(0, 3)),
+            ),
+            (
+                "1.5^2000",
+                Error::Overflow(OperatorType::Pow, Span::new(3, 4)),
+            ),
+            (
+                "(0 - 1.5)^2001",
+                Error::Overflow(OperatorType::Pow, Span::new(9, 10)),
+            ),
+        ] {
+            let error = doeval(input, strict.clone()).unwrap_err();
+            assert_eq!(&error.error, expected, "Checking [{}]", input);
+
+            // Without strict mode, these all produce a value
+            assert!(doeval(input, EvaluationContext::default()).is_ok());
+        }
+
+        // Complex arguments may still have complex results
+        let (result, _) = doeval("sqrt(-4 i)", strict.clone()).unwrap();
+        assert!(matches!(result, Number::Complex(_)));
+
+        // Integers too large to factor or test for primality are errors either way
+        for (input, kind, len) in &[
+            ("factor(2^64 + 1)", OperatorType::Factor, 6),
+            ("isprime(10^400 + 1)", OperatorType::IsPrime, 7),
+            ("nextprime(2^1024)", OperatorType::NextPrime, 9),
+        ] {
+            for context in [strict.clone(), EvaluationContext::default()] {
+                let error = doeval(input, context).unwrap_err();
+                let expected = Error::Domain(*kind, Span::new(0, *len));
+                assert_eq!(error.error, expected, "Checking [{}]", input);
+            }
+        }
+    }
+
     #[test]
     fn test_doeval_errors() {
         [
-            ("1 + 2 + 3 + h", Error::Parsing(12)),
-            ("h", Error::Parsing(0)),
-            ("(1", Error::MismatchingParens),
-            ("3 + $a", Error::UnknownVariable(4)),
+            ("1 + 2 + 3 + h", Error::Parsing(Span::new(12, 13))),
+            ("h", Error::Parsing(Span::new(0, 1))),
+            ("(1", Error::MismatchingParens(Span::new(0, 1))),
+            ("3 + $a", Error::UnknownVariable(Span::new(4, 6))),
+            (
+                "gcd(1.5, 3)",
+                Error::NotInteger(OperatorType::Gcd, Span::new(0, 3)),
+            ),
+            (
+                "1 + nCr(5, i)",
+                Error::NotInteger(OperatorType::Choose, Span::new(4, 7)),
+            ),
+            // Calls take exactly the arguments inside their parentheses
+            (
+                "5 + hypot(3)",
+                Error::Operand(InnerFunction::Builtin(OperatorType::Hypot), Span::new(4, 9)),
+            ),
+            (
+                "sin(1, 2)",
+                Error::Operand(InnerFunction::Builtin(OperatorType::Sin), Span::new(0, 3)),
+            ),
+            (
+                "if(1, 2)",
+                Error::Operand(InnerFunction::Builtin(OperatorType::If), Span::new(0, 2)),
+            ),
+            (
+                "hypot 3",
+                Error::Operand(InnerFunction::Builtin(OperatorType::Hypot), Span::new(0, 5)),
+            ),
+            (
+                "max(1 +, 2)",
+                Error::Operand(InnerFunction::Builtin(OperatorType::Add), Span::new(6, 7)),
+            ),
+            // Operator names are whole words, `rem` isn't `re` followed by metres
+            ("5 rem 2", Error::Parsing(Span::new(2, 5))),
+            ("max(1,,2)", Error::Parsing(Span::new(6, 7))),
+            ("max(, 1)", Error::Parsing(Span::new(4, 5))),
+            ("max(1, 2,)", Error::Parsing(Span::new(8, 9))),
         ]
         .iter()
         .for_each(|(a, b)| {
@@ -277,16 +626,16 @@ mod tests {
 
     #[test]
     fn test_vars() {
-        let test_vars = vec![
+        let test_vars = SymbolTable::from([
             Variable {
                 repr: String::from('v'),
-                value: 5.0,
+                value: 5.0.into(),
             },
             Variable {
                 repr: String::from("pi"),
-                value: 7.0,
+                value: 7.0.into(),
             },
-        ];
+        ]);
 
         [
             (
@@ -304,14 +653,14 @@ mod tests {
                         inner: &test_vars[0],
                     },
                     Token::operator(OperatorType::Add),
-                    Token::Number { value: 5.0 },
+                    Token::Number { value: 5.0.into() },
                 ],
             ),
             (
                 "  5 +    $v    ",
                 10.0,
                 vec![
-                    Token::Number { value: 5.0 },
+                    Token::Number { value: 5.0.into() },
                     Token::operator(OperatorType::Add),
                     Token::Variable {
                         inner: &test_vars[0],
@@ -340,13 +689,13 @@ mod tests {
                 Err(e) => panic!("error! {:?}; {}", e, a),
             };
             assert_eq!(&tokens, c, "Checking tokenization of [{}]", a);
-            assert_same!(result, *b, "Checking evaluation of [{}]", a);
+            assert_same!(result.to_f64(), *b, "Checking evaluation of [{}]", a);
         });
     }
 
     #[test]
     fn fail_vars() {
-        vec![("3 + $a", Error::UnknownVariable(4))]
+        [("3 + $a", Error::UnknownVariable(Span::new(4, 6)))]
             .iter()
             .for_each(|(a, b)| {
                 assert_eq!(
@@ -355,4 +704,273 @@ mod tests {
                 );
             });
     }
+
+    #[test]
+    fn test_doeval_rational() {
+        let funcs = SymbolTable::from([Function::new(
+            "half".to_string(),
+            vec!["x".to_string()],
+            "$x / 2".to_string(),
+        )]);
+        let context = EvaluationContext {
+            funcs: &funcs,
+            options: Options {
+                numeric: NumericMode::Rational,
+                ..Options::default()
+            },
+            ..EvaluationContext::default()
+        };
+        for (input, expected) in &[
+            ("0.1 + 0.2", "3/10"),
+            ("1/3*3", "1"),
+            ("1/3 + 1/6", "1/2"),
+            ("(2/3)^-2", "9/4"),
+            ("(-7/2) % 2", "-3/2"),
+            ("1/3 == 2/6", "1"),
+            ("if(1/3 < 0.33, 1, 1/7)", "1/7"),
+            ("#half(1/3)", "1/6"),
+            ("max(1/2, 2/3, 0.6)", "2/3"),
+            ("1e20 + 1", "100000000000000000001"),
+            ("6.022e23 / 1e23", "3011/500"),
+        ] {
+            let (result, _) = doeval(input, context.clone()).unwrap();
+            assert!(result.is_exact(), "Checking exactness of [{}]", input);
+            assert_eq!(
+                result.to_string(),
+                *expected,
+                "Checking evaluation of [{}]",
+                input
+            );
+        }
+
+        // Operators without an exact implementation fall back to floats
+        for (input, expected) in &[
+            ("sqrt(4)", 2.0),
+            ("1/2 + pi", 0.5 + std::f64::consts::PI),
+            ("2^0.5", 2.0_f64.sqrt()),
+        ] {
+            let (result, _) = doeval(input, context.clone()).unwrap();
+            assert!(
+                matches!(result, Number::Float(_)),
+                "Checking fallback of [{}]",
+                input
+            );
+            assert_same!(
+                result.to_f64(),
+                *expected,
+                "Checking evaluation of [{}]",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_doeval_big_integers() {
+        for (input, expected) in &[
+            ("100!", "93326215443944152681699238856266700490715968264381621468592963895217599993229915608941463976156518286253697920827223758251185210916864000000000000000000000000"),
+            ("2^512", "13407807929942597099574024998205846127479365820592393377723561443721764030073546976801874298166903427690031858186486050853753882811946569946433649006084096"),
+            ("2^512 % 1000000007", "418385479"),
+            ("3^200 mod (2^127 - 1)", "10810968933129975378600013865352026249"),
+            ("6 / 3", "2"),
+            ("gcd(2^100, 6^50)", "1125899906842624"),
+            ("nCr(100, 50)", "100891344545564193334812497256"),
+            ("nextprime(2^64)", "18446744073709551629"),
+            ("factor(360)", "2^3 × 3^2 × 5"),
+            ("factor(2^64 - 1)", "3 × 5 × 17 × 257 × 641 × 65537 × 6700417"),
+            ("-2^100 // 3", "-422550200076076467165567735126"),
+            ("9007199254740993", "9007199254740993"),
+            ("9_007_199_254_740_993 - 9007199254740992", "1"),
+            ("0xFFFF_FFFF_FFFF_FFFF_FF + 1", "4722366482869645213696"),
+            ("1e3 + 1", "1001"),
+        ] {
+            let (result, _) = doeval(input, EvaluationContext::default()).unwrap();
+            assert!(matches!(result, Number::Integer(_) | Number::Factored(_)), "Checking exactness of [{}]", input);
+            assert_eq!(result.to_string(), *expected, "Checking evaluation of [{}]", input);
+        }
+
+        // Fractional results are floats outside of rational mode
+        let (result, _) = doeval("7 / 2", EvaluationContext::default()).unwrap();
+        assert_eq!(result, Number::Float(3.5));
+        let (result, _) = doeval("2^-1", EvaluationContext::default()).unwrap();
+        assert_eq!(result, Number::Float(0.5));
+        // Scientific notation is float syntax
+        let (result, _) = doeval("1e20 + 1", EvaluationContext::default()).unwrap();
+        assert_eq!(result, Number::Float(1e20));
+        let (result, _) = doeval("6.022e23", EvaluationContext::default()).unwrap();
+        assert_eq!(result, Number::Float(6.022e23));
+    }
+
+    #[test]
+    fn test_doeval_complex() {
+        for (input, expected) in &[
+            ("sqrt(-1)", "0.000 + 1.000i"),
+            ("i^2", "-1.000"),
+            ("(3 + 4i) * (1 - 2i)", "11.000 - 2.000i"),
+            ("(1 + i) / (1 - i)", "0.000 + 1.000i"),
+            ("abs(3 + 4i)", "5.000"),
+            ("re(2 - 5i) + im(2 - 5i)", "-3.000"),
+            ("conj(2 - 5i)", "2.000 + 5.000i"),
+            ("ln(-1)", "0.000 + 3.142i"),
+            ("e^(i pi)", "-1.000 + 0.000i"),
+            ("arg(-2)", "3.142"),
+            ("2i == i + i", "1.000"),
+        ] {
+            let (result, _) = doeval(input, EvaluationContext::default()).unwrap();
+            assert_eq!(
+                format!("{:.3}", result),
+                *expected,
+                "Checking evaluation of [{}]",
+                input
+            );
+        }
+
+        // Operators without a complex implementation are undefined for complex arguments
+        for (input, kind, span) in &[
+            ("max(i, 1)", OperatorType::Max, Span::new(0, 3)),
+            ("median(1, 2, 3i)", OperatorType::Median, Span::new(0, 6)),
+            ("i < 1", OperatorType::Less, Span::new(2, 3)),
+            ("floor(2 + i)", OperatorType::Floor, Span::new(0, 5)),
+        ] {
+            for &strict in &[false, true] {
+                let mut context = EvaluationContext::default();
+                context.options.strict = strict;
+                let error = doeval(input, context).unwrap_err();
+                assert_eq!(
+                    error.error,
+                    Error::Domain(*kind, *span),
+                    "Checking error of [{}]",
+                    input
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_doeval_units() {
+        for (input, expected) in &[
+            ("5 m / 2 s", "2.500 m/s"),
+            ("3 kg * 9.81 m/s^2", "29.430 kg·m/s^2"),
+            ("12 in to cm", "30.480 cm"),
+            ("60 mi/hr to km/hr", "96.561 km/hr"),
+            ("1 km + 500 m", "1.500 km"),
+            ("2 minutes + 30 s to s", "150.000 s"),
+            ("sqrt(16 m^2)", "4.000 m"),
+            ("-2 N * 3 m to J", "-6.000 J"),
+            ("1 km / 1 m", "1000.000"),
+            ("1 ft < 1 m", "1.000"),
+            ("max(1 m, 90 cm, 2 ft)", "1.000 m"),
+            ("1 μs to ns", "1000.000 ns"),
+        ] {
+            let (result, _) = doeval(input, EvaluationContext::default()).unwrap();
+            assert_eq!(
+                format!("{:.3}", result),
+                *expected,
+                "Checking evaluation of [{}]",
+                input
+            );
+        }
+
+        // Errors point at the operator
+        for (input, kind, span) in &[
+            ("1 m + 1 s", OperatorType::Add, Span::new(4, 5)),
+            ("1 m + 1", OperatorType::Add, Span::new(4, 5)),
+            ("sin(1 m)", OperatorType::Sin, Span::new(0, 3)),
+            ("1 m to s", OperatorType::Convert, Span::new(4, 6)),
+            ("5 to 3", OperatorType::Convert, Span::new(2, 4)),
+            ("sqrt(2 m)", OperatorType::Sqrt, Span::new(0, 4)),
+            ("2 ^ (1 m)", OperatorType::Pow, Span::new(2, 3)),
+            // Dimensions too large to represent
+            ("(1 L)^50", OperatorType::Pow, Span::new(5, 6)),
+            ("(1 m)^128 / (1 m)^-128", OperatorType::Pow, Span::new(5, 6)),
+            (
+                "(1 m)^100 * (1 m)^100",
+                OperatorType::Mul,
+                Span::new(10, 11),
+            ),
+        ] {
+            let error = doeval(input, EvaluationContext::default()).unwrap_err();
+            let expected = Error::Dimension(*kind, *span);
+            assert_eq!(error.error, expected, "Checking [{}]", input);
+        }
+
+        // Complex values have no units, and conversions are to units rather than quantities
+        for (input, kind, span) in &[
+            ("2i * 1 m", OperatorType::Mul, Span::new(3, 4)),
+            ("12 in to 2 cm", OperatorType::Convert, Span::new(6, 8)),
+        ] {
+            let error = doeval(input, EvaluationContext::default()).unwrap_err();
+            let expected = Error::Domain(*kind, *span);
+            assert_eq!(error.error, expected, "Checking [{}]", input);
+        }
+    }
+
+    #[test]
+    fn test_doeval_bare() {
+        let vars = SymbolTable::from([
+            Variable {
+                repr: "x".to_string(),
+                value: 3.0.into(),
+            },
+            Variable {
+                repr: "xy".to_string(),
+                value: 10.0.into(),
+            },
+            Variable {
+                repr: "m".to_string(),
+                value: 2.0.into(),
+            },
+            Variable {
+                repr: "sq".to_string(),
+                value: 0.0.into(),
+            },
+        ]);
+        let funcs = SymbolTable::from([
+            Function::new("f".to_string(), vec!["a".to_string()], "a^2".to_string()),
+            Function::new("sq".to_string(), vec!["a".to_string()], "a^2".to_string()),
+        ]);
+        let context = EvaluationContext {
+            vars: Scope::new(&vars),
+            funcs: &funcs,
+            options: Options {
+                identifiers: IdentifierMode::Bare,
+                ..Options::default()
+            },
+            ..EvaluationContext::default()
+        };
+
+        for (input, expected) in &[
+            ("x + xy", 13.0),
+            ("2x + f(x)", 15.0),
+            ("f x + 1", 10.0),
+            ("sin(pi) + max(x, 1)", 3.0),
+            ("5 m", 10.0),
+            ("$x + #f($x)", 12.0),
+            ("2 pi / tau + e - e", 1.0),
+        ] {
+            let (result, _) = doeval(input, context.clone()).unwrap();
+            assert_same!(
+                result.to_f64(),
+                *expected,
+                "Checking evaluation of [{}]",
+                input
+            );
+        }
+
+        for (input, error) in &[
+            ("1 + sq(2)", Error::Ambiguous(Span::new(4, 6))),
+            ("y + 1", Error::UnknownVariable(Span::new(0, 1))),
+            ("1 + q(2)", Error::UnknownFunction(Span::new(4, 5))),
+            ("sinx", Error::UnknownVariable(Span::new(0, 4))),
+        ] {
+            let result = doeval(input, context.clone()).unwrap_err();
+            assert_eq!(result.error, *error, "Checking [{}]", input);
+        }
+
+        // Bare identifiers aren't resolved by default
+        let context = EvaluationContext {
+            vars: Scope::new(&vars),
+            ..EvaluationContext::default()
+        };
+        assert!(doeval("x + 1", context).is_err());
+    }
 }
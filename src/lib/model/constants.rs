@@ -1,13 +1,16 @@
 #![allow(clippy::non_ascii_literal)]
 
-use super::representable::{get_by_repr, Representable};
+use num_complex::Complex64;
+
+use super::representable::{get_longest_by_repr, Representable};
 
 #[allow(clippy::upper_case_acronyms)]
-#[derive(PartialEq, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum ConstantType {
     PI,
     E,
     Tau,
+    I,
 }
 
 /// Represents a constant
@@ -15,7 +18,8 @@ pub enum ConstantType {
 pub struct Constant {
     pub kind: ConstantType,
     pub repr: &'static [&'static str],
-    pub value: f64,
+    /// The constant's value, real constants have no imaginary part
+    pub value: Complex64,
 }
 
 impl Representable for Constant {
@@ -28,21 +32,31 @@ static CONSTANTS: &[Constant] = &[
     Constant {
         kind: ConstantType::PI,
         repr: &["π", "pi"],
-        value: std::f64::consts::PI,
+        value: Complex64::new(std::f64::consts::PI, 0.0),
     },
     Constant {
         kind: ConstantType::Tau,
         repr: &["τ", "tau"],
-        value: std::f64::consts::TAU,
+        value: Complex64::new(std::f64::consts::TAU, 0.0),
     },
     Constant {
         kind: ConstantType::E,
         repr: &["e"],
-        value: std::f64::consts::E,
+        value: Complex64::new(std::f64::consts::E, 0.0),
+    },
+    Constant {
+        kind: ConstantType::I,
+        repr: &["i"],
+        value: Complex64::new(0.0, 1.0),
     },
 ];
 
 impl Constant {
+    /// Every builtin `Constant`
+    pub fn all() -> &'static [Self] {
+        CONSTANTS
+    }
+
     /// Get a `Constant` by its `ConstantType`
     pub fn by_type(kind: ConstantType) -> &'static Self {
         CONSTANTS.iter().find(|c| c.kind == kind).unwrap()
@@ -50,7 +64,7 @@ impl Constant {
 
     /// Get a `Constant` by one of its string representations
     pub fn by_repr(repr: &str) -> Option<(&'static Self, usize)> {
-        get_by_repr(repr, CONSTANTS)
+        get_longest_by_repr(repr, CONSTANTS, false)
     }
 
     /// Determines if the next sequence is a `Constant`
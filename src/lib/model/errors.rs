@@ -1,43 +1,80 @@
-use super::{functions::Function, operators::OperatorType};
+use std::{error, fmt};
 
-#[derive(Debug, PartialEq)]
+use super::{
+    functions::Function,
+    number::Number,
+    operators::{Arity, Operator, OperatorType, FUNCTIONAL_STYLE_OPERATORS},
+    span::Span,
+};
+
+#[derive(Debug, PartialEq, Eq)]
 pub enum InnerFunction {
     Builtin(OperatorType),
     User(Function),
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub enum ErrorContext {
+    #[default]
     Main,
     Scoped(Function),
 }
 
-impl Default for ErrorContext {
-    fn default() -> Self {
-        Self::Main
-    }
-}
-
-#[derive(Debug, PartialEq)]
+/// An error, along with the span of the source that caused it
+#[derive(Debug, PartialEq, Eq)]
 pub enum Error {
-    /// Arises when an expression failed to pase at a particular index
-    Parsing(usize),
+    /// Arises when an expression failed to pase at a particular location
+    Parsing(Span),
 
     /// Arises when an `Operator` failed to compute a value. e.g. when there are insufficient arguments.
-    Operand(InnerFunction),
+    /// The span is that of the operator.
+    Operand(InnerFunction, Span),
+
+    /// Arises when an expression, e.g. `()`, doesn't produce exactly one value
+    EmptyStack(Span),
+
+    /// Arises when parentheses mismatch. The span is that of the unmatched paren.
+    MismatchingParens(Span),
+
+    /// Arises when an unknown `Variable` is found at a particular location
+    UnknownVariable(Span),
+
+    /// Arises when an unknown `Function` is found at a particular location
+    UnknownFunction(Span),
 
-    /// Arises when the stack is empty for some reason
-    EmptyStack,
+    /// Arises when functions are nested too deeply. The span covers the code of the innermost function.
+    RecursionLimit(Span),
 
-    /// Arises when parentheses mismatch
-    MismatchingParens,
+    /// Arises when an evaluation performs more operations than its `Limits` allow.
+    /// The span is that of the operation that went over.
+    StepLimit(Span),
 
-    /// Arises when an unknown `Variable` is found at a particular index
-    UnknownVariable(usize),
+    /// Arises when an evaluation runs for longer than its `Limits` allow.
+    /// The span is that of the operation that was running.
+    Timeout(Span),
 
-    UnknownFunction(usize),
+    /// Arises when an `Operator` is applied to values with incompatible units, e.g. `1 m + 1 s`
+    Dimension(OperatorType, Span),
 
-    RecursionLimit,
+    /// Arises when an `Operator` that only accepts integers is given anything else, e.g. `gcd(1.5, 3)`
+    NotInteger(OperatorType, Span),
+
+    /// Arises in strict mode when dividing by zero, e.g. `1 / 0`, `5 % 0`, or `0^-1`.
+    /// The span is that of the operator.
+    DivisionByZero(Span),
+
+    /// Arises when an `Operator` is applied to values outside of its domain, e.g. `1 m * 2i` or `max(i, 1)`,
+    /// or in strict mode to real values, e.g. `sqrt(-4)`
+    Domain(OperatorType, Span),
+
+    /// Arises in strict mode when an `Operator` produces an infinite result from finite values, e.g. `exp(1000)`
+    Overflow(OperatorType, Span),
+
+    /// Arises when a bare identifier names both a variable and a function
+    Ambiguous(Span),
+
+    /// Arises when a statement is a malformed assignment, e.g. `$x =` or `$x = $y = 1`
+    Assignment(Span),
 }
 
 impl Error {
@@ -45,7 +82,159 @@ impl Error {
         ContextualError {
             context,
             error: self,
+            trace: Vec::new(),
+        }
+    }
+
+    /// The span of the source that caused this error
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::Parsing(span)
+            | Self::Operand(_, span)
+            | Self::EmptyStack(span)
+            | Self::MismatchingParens(span)
+            | Self::UnknownVariable(span)
+            | Self::UnknownFunction(span)
+            | Self::RecursionLimit(span)
+            | Self::StepLimit(span)
+            | Self::Timeout(span)
+            | Self::Dimension(_, span)
+            | Self::NotInteger(_, span)
+            | Self::DivisionByZero(span)
+            | Self::Domain(_, span)
+            | Self::Overflow(_, span)
+            | Self::Ambiguous(span)
+            | Self::Assignment(span) => *span,
+        }
+    }
+
+    const fn span_mut(&mut self) -> &mut Span {
+        match self {
+            Self::Parsing(span)
+            | Self::Operand(_, span)
+            | Self::EmptyStack(span)
+            | Self::MismatchingParens(span)
+            | Self::UnknownVariable(span)
+            | Self::UnknownFunction(span)
+            | Self::RecursionLimit(span)
+            | Self::StepLimit(span)
+            | Self::Timeout(span)
+            | Self::Dimension(_, span)
+            | Self::NotInteger(_, span)
+            | Self::DivisionByZero(span)
+            | Self::Domain(_, span)
+            | Self::Overflow(_, span)
+            | Self::Ambiguous(span)
+            | Self::Assignment(span) => span,
+        }
+    }
+
+    /// Whether this error points at a particular part of its source,
+    /// rather than spanning all of it, like `EmptyStack` and `RecursionLimit`
+    pub const fn is_located(&self) -> bool {
+        !matches!(self, Self::EmptyStack(_) | Self::RecursionLimit(_))
+    }
+
+    /// Replaces the span of this error
+    #[must_use]
+    pub const fn with_span(mut self, span: Span) -> Self {
+        *self.span_mut() = span;
+        self
+    }
+
+    /// Shifts the span of this error by `offset` characters
+    #[must_use]
+    pub const fn offset(self, offset: usize) -> Self {
+        let span = self.span().offset(offset);
+        self.with_span(span)
+    }
+}
+
+/// Messages for located errors end with their index, e.g. `Unknown variable at index 4`
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parsing(_) => write!(f, "Couldn't parse the token"),
+            Self::Operand(InnerFunction::Builtin(kind), _) => {
+                match Operator::by_type(*kind).arity {
+                    Arity::Fixed(arity) if FUNCTIONAL_STYLE_OPERATORS.contains(kind) => write!(
+                        f,
+                        "Couldn't evaluate. Operator [{:?}] requires [{}] arguments",
+                        kind, arity
+                    ),
+                    _ => write!(
+                        f,
+                        "Couldn't evaluate. Operator [{:?}] requires an operand",
+                        kind
+                    ),
+                }
+            }
+            Self::Operand(InnerFunction::User(func), _) => write!(
+                f,
+                "Couldn't evaluate. Function #{} requires [{}] arguments",
+                func.name,
+                func.args.len()
+            ),
+            Self::EmptyStack(_) => write!(f, "Couldn't evaluate. Stack was empty?"),
+            Self::MismatchingParens(_) => write!(f, "Couldn't evaluate. Mismatched parens"),
+            Self::UnknownVariable(_) => write!(f, "Unknown variable"),
+            Self::UnknownFunction(_) => write!(f, "Unknown function"),
+            Self::RecursionLimit(_) => write!(f, "Exceeded recursion limit"),
+            Self::StepLimit(_) => write!(f, "Exceeded step limit"),
+            Self::Timeout(_) => write!(f, "Exceeded time limit"),
+            Self::Dimension(kind, _) => write!(
+                f,
+                "Couldn't evaluate. Operator [{:?}] was given incompatible units",
+                kind
+            ),
+            Self::NotInteger(kind, _) => write!(
+                f,
+                "Couldn't evaluate. Operator [{:?}] requires integers",
+                kind
+            ),
+            Self::DivisionByZero(_) => write!(f, "Couldn't evaluate. Division by zero"),
+            Self::Domain(kind, _) => write!(
+                f,
+                "Couldn't evaluate. Operator [{:?}] was given a value outside of its domain",
+                kind
+            ),
+            Self::Overflow(kind, _) => {
+                write!(f, "Couldn't evaluate. Operator [{:?}] overflowed", kind)
+            }
+            Self::Ambiguous(_) => write!(f, "Name refers to both a variable and a function"),
+            Self::Assignment(_) => write!(f, "Couldn't assign. Malformed assignment statement"),
+        }?;
+        if self.is_located() {
+            write!(f, " at index {}", self.span().start)?;
         }
+        Ok(())
+    }
+}
+
+impl error::Error for Error {}
+
+/// A call to a user function that was in progress when an error occurred
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// The name of the function
+    pub name: String,
+    /// The values the function was called with
+    pub args: Vec<Number>,
+    /// The span of the call in the caller's code
+    pub span: Span,
+}
+
+/// Written as the call, e.g. `#f(1, 2) at index 4`
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}(", self.name)?;
+        for (idx, arg) in self.args.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", arg)?;
+        }
+        write!(f, ") at index {}", self.span.start)
     }
 }
 
@@ -53,6 +242,9 @@ impl Error {
 pub struct ContextualError {
     pub context: ErrorContext,
     pub error: Error,
+    /// The calls leading up to the error, innermost first.
+    /// The span of each frame is in the code of the next one, and the last is in the main expression.
+    pub trace: Vec<Frame>,
 }
 
 impl ContextualError {
@@ -62,10 +254,81 @@ impl ContextualError {
     // > rustc(E0493)
     // It may be possible to fix this later
     #[allow(clippy::missing_const_for_fn)]
+    #[must_use]
     pub fn with_context(self, context: ErrorContext) -> Self {
-        Self {
-            context,
-            error: self.error,
+        Self { context, ..self }
+    }
+
+    /// Record that this error occurred inside of the call described by `frame`
+    #[must_use]
+    pub fn in_call(mut self, frame: Frame) -> Self {
+        self.trace.push(frame);
+        self
+    }
+
+    /// Shifts the span that points into the main expression by `offset` characters.
+    /// That's the span of the outermost call if there is one, or otherwise the span of the error.
+    #[must_use]
+    pub fn offset(mut self, offset: usize) -> Self {
+        if let Some(frame) = self.trace.last_mut() {
+            frame.span = frame.span.offset(offset);
+        } else if self.context == ErrorContext::Main {
+            self.error = self.error.offset(offset);
         }
+        self
+    }
+}
+
+/// Errors in the main expression display as the error itself.
+/// Errors inside of a function display as the function, with the error as their `source`.
+impl fmt::Display for ContextualError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.context {
+            ErrorContext::Main => fmt::Display::fmt(&self.error, f),
+            ErrorContext::Scoped(func) => write!(f, "In function #{}", func.name),
+        }
+    }
+}
+
+impl error::Error for ContextualError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self.context {
+            ErrorContext::Main => None,
+            ErrorContext::Scoped(_) => Some(&self.error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::error::Error as _;
+
+    use super::{Error, ErrorContext};
+    use crate::model::{functions::Function, operators::OperatorType, span::Span};
+
+    #[test]
+    fn test_display() {
+        let error = Error::Dimension(OperatorType::Add, Span::new(4, 5));
+        assert_eq!(
+            error.to_string(),
+            "Couldn't evaluate. Operator [Add] was given incompatible units at index 4"
+        );
+        let error = Error::RecursionLimit(Span::new(0, 7));
+        assert_eq!(error.to_string(), "Exceeded recursion limit");
+    }
+
+    #[test]
+    fn test_source() {
+        let error = Error::UnknownVariable(Span::new(0, 2)).with_context(ErrorContext::Main);
+        assert_eq!(error.to_string(), "Unknown variable at index 0");
+        assert!(error.source().is_none());
+
+        let func = Function::new("f".to_string(), vec![], "$x".to_string());
+        let error =
+            Error::UnknownVariable(Span::new(0, 2)).with_context(ErrorContext::Scoped(func));
+        assert_eq!(error.to_string(), "In function #f");
+        let source = error.source().unwrap();
+        assert_eq!(source.to_string(), "Unknown variable at index 0");
     }
 }
@@ -0,0 +1,306 @@
+use super::{
+    constants::Constant,
+    errors::{Error, InnerFunction},
+    functions::Functions,
+    number::Literal,
+    operators::{Arity, Operator, FUNCTIONAL_STYLE_OPERATORS},
+    span::{Span, Spanned},
+    tokens::{ParenType, Token},
+    units::PrefixedUnit,
+};
+
+/// The target of an `Expr::Call`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Callee {
+    Builtin(&'static Operator),
+    /// A user-defined function, referenced by name
+    User(String),
+}
+
+/// An expression tree.
+/// Variables and user functions are referenced by name, so a tree isn't tied to the context it was built in.
+/// Operators keep the span of their token in the source, for errors.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Literal),
+    Constant(&'static Constant),
+    Unit(PrefixedUnit),
+    /// A variable, referenced by name
+    Variable(String),
+    /// A prefix or postfix operator, e.g. `-a` or `a!`
+    Unary {
+        op: &'static Operator,
+        operand: Box<Self>,
+        span: Span,
+    },
+    /// An infix operator, e.g. `a + b`
+    Binary {
+        op: &'static Operator,
+        lhs: Box<Self>,
+        rhs: Box<Self>,
+        span: Span,
+    },
+    /// A functional-style operator or a user-defined function, e.g. `max(a, b)` or `#foo(a)`
+    Call {
+        callee: Callee,
+        args: Vec<Self>,
+        span: Span,
+    },
+}
+
+impl Expr {
+    /// Build an expression tree from a list of tokens in Reverse-Polish-Notation
+    /// * `tokens` - The tokens, as produced by `rpn`
+    ///
+    /// ## Errors
+    /// Returns an `Error::Operand` if an operator doesn't have enough operands, or a call has the wrong number
+    /// of arguments, or an `Error::EmptyStack` if the tokens don't form exactly one expression
+    pub(crate) fn from_rpn(tokens: &[Spanned<Token>]) -> Result<Self, Error> {
+        let mut stack: Vec<Self> = Vec::new();
+
+        // The stack heights at which the arguments of calls begin
+        let mut markers: Vec<usize> = Vec::new();
+
+        for Spanned { inner: token, span } in tokens {
+            let span = *span;
+            let expr = match token {
+                Token::Number { value } => Self::Literal(value.clone()),
+                Token::Constant { inner } => Self::Constant(Constant::by_type(inner.kind)),
+                Token::Unit { inner } => Self::Unit(*inner),
+                Token::Variable { inner } => Self::Variable(inner.repr.clone()),
+                Token::Operator { inner } => {
+                    let start = match inner.arity() {
+                        // Calls take exactly the arguments written inside their parentheses
+                        Arity::Fixed(arity) if inner.is_functional() => {
+                            markers.pop().filter(|start| stack.len() - start == arity)
+                        }
+                        // Other operators can't take operands from outside of the call they're in
+                        Arity::Fixed(arity) => stack
+                            .len()
+                            .checked_sub(arity)
+                            .filter(|start| markers.last().is_none_or(|marker| start >= marker)),
+                        // Variadic operators require at least one argument
+                        Arity::Variadic => markers.pop().filter(|start| *start < stack.len()),
+                    };
+                    let Some(start) = start else {
+                        let inner = match inner {
+                            Functions::Builtin(b) => InnerFunction::Builtin(b.kind),
+                            Functions::User(func) => InnerFunction::User((*func).clone()),
+                        };
+                        return Err(Error::Operand(inner, span));
+                    };
+
+                    // Takes this operator's arguments from the top of the stack
+                    let mut args: Vec<Self> = stack.drain(start..).collect();
+
+                    match inner {
+                        Functions::Builtin(op) => {
+                            let op = Operator::by_type(op.kind);
+                            if FUNCTIONAL_STYLE_OPERATORS.contains(&op.kind) {
+                                Self::Call {
+                                    callee: Callee::Builtin(op),
+                                    args,
+                                    span,
+                                }
+                            } else if args.len() == 1 {
+                                Self::Unary {
+                                    op,
+                                    operand: Box::new(args.remove(0)),
+                                    span,
+                                }
+                            } else {
+                                let rhs = Box::new(args.remove(1));
+                                let lhs = Box::new(args.remove(0));
+                                Self::Binary { op, lhs, rhs, span }
+                            }
+                        }
+                        Functions::User(func) => Self::Call {
+                            callee: Callee::User(func.name.clone()),
+                            args,
+                            span,
+                        },
+                    }
+                }
+                Token::Paren {
+                    kind: ParenType::Left,
+                } => {
+                    markers.push(stack.len());
+                    continue;
+                }
+                Token::Paren { .. } | Token::Comma => continue,
+            };
+            stack.push(expr);
+        }
+
+        if stack.len() == 1 {
+            return Ok(stack.remove(0));
+        }
+        let span = tokens.iter().map(|token| token.span).reduce(Span::to);
+        Err(Error::EmptyStack(span.unwrap_or_default()))
+    }
+
+    /// The span of this expression's operator, if it has one
+    pub const fn span(&self) -> Option<Span> {
+        match self {
+            Self::Literal(_) | Self::Constant(_) | Self::Unit(_) | Self::Variable(_) => None,
+            Self::Unary { span, .. } | Self::Binary { span, .. } | Self::Call { span, .. } => {
+                Some(*span)
+            }
+        }
+    }
+
+    /// The sub-expressions directly beneath this one
+    pub fn children(&self) -> Vec<&Self> {
+        match self {
+            Self::Literal(_) | Self::Constant(_) | Self::Unit(_) | Self::Variable(_) => vec![],
+            Self::Unary { operand, .. } => vec![operand],
+            Self::Binary { lhs, rhs, .. } => vec![lhs, rhs],
+            Self::Call { args, .. } => args.iter().collect(),
+        }
+    }
+
+    /// Lists the names of the variables referenced in this expression, without duplicates, in order of appearance
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names = vec![];
+        self.walk(&mut |expr| {
+            if let Self::Variable(name) = expr {
+                if !names.contains(&name.as_str()) {
+                    names.push(name.as_str());
+                }
+            }
+        });
+        names
+    }
+
+    /// Lists the names of the user functions called in this expression, without duplicates, in order of appearance
+    pub fn functions(&self) -> Vec<&str> {
+        let mut names = vec![];
+        self.walk(&mut |expr| {
+            if let Self::Call {
+                callee: Callee::User(name),
+                ..
+            } = expr
+            {
+                if !names.contains(&name.as_str()) {
+                    names.push(name.as_str());
+                }
+            }
+        });
+        names
+    }
+
+    /// Visit this expression and all of its sub-expressions, parents before children
+    pub fn walk<'a, F: FnMut(&'a Self)>(&'a self, visit: &mut F) {
+        visit(self);
+        for child in self.children() {
+            child.walk(visit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{Callee, Expr};
+    use crate::{
+        model::{
+            errors::{Error, InnerFunction},
+            functions::Function,
+            operators::{Operator, OperatorType},
+            span::Span,
+            symbols::SymbolTable,
+            variables::{Scope, Variable},
+            EvaluationContext,
+        },
+        rpn::rpn,
+        tokenize_and_transform,
+    };
+
+    fn build(string: &str, context: &EvaluationContext) -> Result<Expr, Error> {
+        let tokens = tokenize_and_transform(string, context).unwrap();
+        Expr::from_rpn(&rpn(&tokens).unwrap())
+    }
+
+    #[test]
+    fn test_from_rpn() {
+        let expr = build("1 + 2 * -3!", &EvaluationContext::default()).unwrap();
+        assert_eq!(
+            expr,
+            Expr::Binary {
+                op: Operator::by_type(OperatorType::Add),
+                lhs: Box::new(Expr::Literal(1.0.into())),
+                rhs: Box::new(Expr::Binary {
+                    op: Operator::by_type(OperatorType::Mul),
+                    lhs: Box::new(Expr::Literal(2.0.into())),
+                    rhs: Box::new(Expr::Unary {
+                        op: Operator::by_type(OperatorType::Factorial),
+                        operand: Box::new(Expr::Unary {
+                            op: Operator::by_type(OperatorType::Negative),
+                            operand: Box::new(Expr::Literal(3.0.into())),
+                            span: Span::new(8, 9),
+                        }),
+                        span: Span::new(10, 11),
+                    }),
+                    span: Span::new(6, 7),
+                }),
+                span: Span::new(2, 3),
+            }
+        );
+
+        let expr = build("max(1, 2)", &EvaluationContext::default()).unwrap();
+        assert_eq!(
+            expr,
+            Expr::Call {
+                callee: Callee::Builtin(Operator::by_type(OperatorType::Max)),
+                args: vec![Expr::Literal(1.0.into()), Expr::Literal(2.0.into())],
+                span: Span::new(0, 3),
+            }
+        );
+    }
+
+    #[test]
+    fn test_from_rpn_errors() {
+        let result = build("1 +", &EvaluationContext::default());
+        assert_eq!(
+            result,
+            Err(Error::Operand(
+                InnerFunction::Builtin(OperatorType::Add),
+                Span::new(2, 3)
+            ))
+        );
+        let result = build("()", &EvaluationContext::default());
+        assert_eq!(result, Err(Error::EmptyStack(Span::default())));
+    }
+
+    #[test]
+    fn test_variables_and_functions() {
+        let vars = SymbolTable::from([
+            Variable {
+                repr: "x".to_string(),
+                value: 1.0.into(),
+            },
+            Variable {
+                repr: "y".to_string(),
+                value: 2.0.into(),
+            },
+        ]);
+        let funcs = SymbolTable::from([Function::new(
+            "f".to_string(),
+            vec!["a".to_string()],
+            "$a".to_string(),
+        )]);
+        let context = EvaluationContext {
+            vars: Scope::new(&vars),
+            funcs: &funcs,
+            ..EvaluationContext::default()
+        };
+
+        let expr = build("$y + #f($x) * $y + #f(1)", &context).unwrap();
+        assert_eq!(expr.variables(), ["y", "x"]);
+        assert_eq!(expr.functions(), ["f"]);
+
+        let expr = build("sin(pi)", &context).unwrap();
+        assert!(expr.variables().is_empty());
+        assert!(expr.functions().is_empty());
+    }
+}
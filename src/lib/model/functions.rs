@@ -1,11 +1,17 @@
-use crate::doeval;
+use std::{
+    fmt,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use crate::CompiledExpression;
 
 use super::{
     errors::{ContextualError, ErrorContext},
-    operators::{Associativity, Operator},
-    representable::{get_by_repr, Searchable},
-    variables::Variable,
-    EvaluationContext,
+    number::Number,
+    operators::{Arity, Associativity, Operator, FUNCTIONAL_STYLE_OPERATORS},
+    symbols::{next_generation, SymbolTable},
+    variables::{Scope, Variable},
+    EvaluationContext, Generation,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -14,23 +20,35 @@ pub enum Functions<'a> {
     User(&'a Function),
 }
 
-impl<'inner> Functions<'inner> {
+impl Functions<'_> {
     /// Apply this function over a set of arguments and return the result.
     /// This never fails for `Functions::Builtin`.
     ///
     /// ## Errors
     /// `Functions::User` produce errors in the same way as [doeval] can, as these are,
     /// in actuality, nested evaluation contexts
-    pub fn arity(&self) -> usize {
+    pub const fn arity(&self) -> Arity {
         match self {
             Functions::Builtin(op) => op.arity,
-            Functions::User(func) => func.arity(),
+            Functions::User(func) => Arity::Fixed(func.arity()),
         }
     }
+    /// Determines if this is called like a function, with its arguments in parentheses. e.g. `max(1, 2)`
+    pub fn is_functional(&self) -> bool {
+        match self {
+            Functions::Builtin(op) => FUNCTIONAL_STYLE_OPERATORS.contains(&op.kind),
+            Functions::User(_) => true,
+        }
+    }
+    /// Determines if this comes before all of its arguments, like `sin(x)`, `-x`, or `not x`
+    pub fn is_prefix(&self) -> bool {
+        self.is_functional()
+            || (self.arity() == Arity::Fixed(1) && self.associativity() == Associativity::Right)
+    }
     pub const fn precedence(&self) -> u8 {
         match self {
             Functions::Builtin(op) => op.precedence,
-            Functions::User(_) => 4,
+            Functions::User(_) => 8,
         }
     }
     pub const fn associativity(&self) -> Associativity {
@@ -41,71 +59,131 @@ impl<'inner> Functions<'inner> {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Function {
     pub name: String,
     pub args: Vec<String>,
     pub code: String,
+    /// Shared by the tables of this function's arguments, which always have the same names
+    args_generation: u64,
+    compiled: CompiledCode,
 }
 
-impl Searchable for Function {
-    fn search<'a>(&'a self, search: &str) -> Option<(&'a Self, usize)> {
-        // Case sensitive
-        if search.starts_with(&self.name) {
-            Some((self, self.name.chars().count()))
-        } else {
-            None
-        }
+/// The lazily compiled body of a `Function`, with the generation of the context it was compiled in.
+/// This is a cache, not part of the function's identity: it's ignored by comparisons and reset by clones.
+#[derive(Default)]
+struct CompiledCode(Mutex<Option<(Generation, Arc<CompiledExpression>)>>);
+
+impl CompiledCode {
+    /// The compiled body, if it was compiled in a context with `generation`
+    fn get(&self, generation: &Generation) -> Option<Arc<CompiledExpression>> {
+        let cached = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        cached
+            .as_ref()
+            .filter(|(cached, _)| cached == generation)
+            .map(|(_, compiled)| compiled.clone())
+    }
+
+    fn set(&self, generation: Generation, compiled: Arc<CompiledExpression>) {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) = Some((generation, compiled));
+    }
+}
+
+impl Clone for CompiledCode {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl PartialEq for CompiledCode {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for CompiledCode {}
+
+impl fmt::Debug for CompiledCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let compiled = self
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some();
+        write!(f, "[compiled: {}]", compiled)
     }
 }
 
 impl Function {
+    pub fn new(name: String, args: Vec<String>, code: String) -> Self {
+        Self {
+            name,
+            args,
+            code,
+            args_generation: next_generation(),
+            compiled: CompiledCode::default(),
+        }
+    }
     pub fn is(text: &str) -> bool {
         text.starts_with('#')
     }
-    pub fn next_function<'a>(text: &str, funcs: &'a [Self]) -> Option<(&'a Self, usize)> {
-        get_by_repr(text, funcs)
+    /// Finds the function in `funcs` with the longest name that `text` starts with
+    pub fn next_function<'a>(
+        text: &str,
+        funcs: &'a SymbolTable<Self>,
+    ) -> Option<(&'a Self, usize)> {
+        funcs.longest_match(text)
     }
-    pub fn arity(&self) -> usize {
+    pub const fn arity(&self) -> usize {
         self.args.len()
     }
 
-    /// Create the variables required to evaluate this function, including both arguments and scoped variables.
-    /// The list is created such that arguments always come before scoped variables. This is important for correct varible-name resolution.
-    pub fn create_variables(&self, args: &[f64], vars: &[Variable]) -> Vec<Variable> {
-        // Create the arguments for the function
-        let args = self.args.iter().zip(args).map(|(name, value)| Variable {
-            repr: name.clone(),
-            value: *value,
-        });
-
-        // Create a cloned iteration of the scoped variables
-        let scoped = vars.iter().cloned();
-
-        // Merge the function arguments with scoped variables
-        // It's important that the function variables are first
-        // So that variable name resolution prioritizes args
-        args.chain(scoped).collect()
+    /// Create the variables for this function's arguments.
+    /// They are layered over the caller's variables with [`Scope::with_parent`], shadowing those with the same name.
+    pub fn create_variables(&self, args: &[Number]) -> SymbolTable<Variable> {
+        self.args
+            .iter()
+            .zip(args)
+            .map(|(name, value)| Variable {
+                repr: name.clone(),
+                value: value.clone(),
+            })
+            .collect::<SymbolTable<Variable>>()
+            .with_generation(self.args_generation)
     }
 
-    /// Apply this function to a set of arguments
+    /// Apply this function to a set of arguments.
+    /// The function's code is compiled on the first call and the result is reused by subsequent calls,
+    /// until the variables, functions, or options it was compiled with change.
     ///
     /// # Errors
-    /// This function calls into `lib::doeval` and bubbles up and errors occuring from within there.
-    pub fn apply<'a>(
+    /// This function compiles and evaluates the function's code and bubbles up any errors occuring from within there.
+    pub fn apply(
         &self,
-        args: &[f64],
-        context: &EvaluationContext<'a>,
-    ) -> Result<f64, ContextualError> {
-        let vars = self.create_variables(args, context.vars);
+        args: &[Number],
+        context: &EvaluationContext<'_>,
+    ) -> Result<Number, ContextualError> {
+        let args = self.create_variables(args);
 
         let context = EvaluationContext {
-            vars: &vars,
+            vars: Scope::with_parent(&args, &context.vars),
             funcs: context.funcs,
             depth: context.depth + 1,
             context: ErrorContext::Scoped(self.clone()),
+            options: context.options,
+            budget: context.budget.clone(),
         };
 
-        doeval(&self.code, context).map(|(a, _)| a)
+        let generation = context.generation();
+        if let Some(compiled) = self.compiled.get(&generation) {
+            return compiled.eval(context);
+        }
+
+        let compiled = match CompiledExpression::new(&self.code, &context) {
+            Ok(compiled) => Arc::new(compiled),
+            Err(error) => return Err(error.with_context(context.context)),
+        };
+        self.compiled.set(generation, compiled.clone());
+        compiled.eval(context)
     }
 }
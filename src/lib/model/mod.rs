@@ -1,20 +1,190 @@
-use self::{errors::ErrorContext, functions::Function, variables::Variable};
+use std::{
+    cell::Cell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use self::{
+    errors::{Error, ErrorContext},
+    functions::Function,
+    number::NumericMode,
+    span::Span,
+    symbols::SymbolTable,
+    variables::{Scope, Variable},
+};
+use crate::RECURSION_LIMIT;
 
 pub mod constants;
 pub mod errors;
+pub mod expr;
 pub mod functions;
+pub mod number;
 pub mod operators;
+pub mod span;
+pub mod symbols;
 pub mod tokens;
+pub mod units;
 pub mod variables;
 
+mod primes;
 mod representable;
+mod special;
+
+/// How variables and user functions are written
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum IdentifierMode {
+    /// Variables and functions are prefixed with `$` and `#`, e.g. `$x + #f(2)`
+    #[default]
+    Sigil,
+    /// Words are resolved as, in order of precedence: variables and user functions, constants,
+    /// builtin operators, then units. e.g. `x + f(2)`. A name that is both a variable and a function is ambiguous.
+    /// Sigils may still be used.
+    Bare,
+}
+
+/// Bounds on the work a single evaluation may do, for when the input isn't trusted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// How deeply calls to user functions may be nested
+    pub max_depth: u8,
+    /// How many operations, including calls to user functions, may be performed
+    pub max_steps: Option<u64>,
+    /// How long an evaluation may run for. Long-running operators, like `nextprime`, are stopped too
+    pub timeout: Option<Duration>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_depth: RECURSION_LIMIT,
+            max_steps: None,
+            timeout: None,
+        }
+    }
+}
+
+/// The work done by an evaluation so far, shared by every function call within it.
+/// It's reset at the start of each top-level evaluation, so contexts may be reused
+#[derive(Debug)]
+pub struct Budget {
+    steps: Cell<u64>,
+    start: Cell<Instant>,
+}
+
+impl Budget {
+    pub fn new() -> Self {
+        Self {
+            steps: Cell::new(0),
+            start: Cell::new(Instant::now()),
+        }
+    }
+
+    /// The number of operations performed so far
+    pub const fn steps(&self) -> u64 {
+        self.steps.get()
+    }
+
+    /// Start counting again, for a new evaluation
+    pub fn reset(&self) {
+        self.steps.set(0);
+        self.start.set(Instant::now());
+    }
+
+    /// Check the time limit without counting an operation, for operators that do a lot of work in one step.
+    /// The span is filled in from the operator's token during evaluation
+    ///
+    /// ## Errors
+    /// Returns an `Error::Timeout` when the time limit is exceeded
+    pub fn check_time(&self, limits: &Limits) -> Result<(), Error> {
+        if limits
+            .timeout
+            .is_some_and(|timeout| self.start.get().elapsed() > timeout)
+        {
+            return Err(Error::Timeout(Span::default()));
+        }
+        Ok(())
+    }
 
-#[derive(Default, Clone)]
+    /// Count an operation against `limits`
+    /// * `span` - The span of the operation, for errors
+    ///
+    /// ## Errors
+    /// Returns an `Error::StepLimit` or `Error::Timeout` when the limits are exceeded
+    pub fn step(&self, limits: &Limits, span: Span) -> Result<(), Error> {
+        let steps = self.steps.get() + 1;
+        self.steps.set(steps);
+        if limits.max_steps.is_some_and(|max| steps > max) {
+            return Err(Error::StepLimit(span));
+        }
+        self.check_time(limits)
+            .map_err(|error| error.with_span(span))
+    }
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Settings that change how expressions are evaluated
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+    /// How numeric literals are represented
+    pub numeric: NumericMode,
+    /// How variables and functions are written
+    pub identifiers: IdentifierMode,
+    /// Bounds on the work done by each evaluation
+    pub limits: Limits,
+    /// Report division by zero, domain errors, and overflow as errors rather than producing `NaN` or infinity
+    pub strict: bool,
+}
+
+#[derive(Clone)]
 pub struct EvaluationContext<'a> {
-    pub vars: &'a [Variable],
-    pub funcs: &'a [Function],
+    pub vars: Scope<'a>,
+    pub funcs: &'a SymbolTable<Function>,
     pub context: ErrorContext,
     pub depth: u8,
+    pub options: Options,
+    /// Shared with the contexts of the functions called during an evaluation, so that their work counts too
+    pub budget: Rc<Budget>,
+}
+
+/// Identifies the names and options an expression is compiled against.
+/// Compiling the same source in contexts with the same generation gives the same expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Generation {
+    vars: Vec<u64>,
+    funcs: u64,
+    options: Options,
+}
+
+impl EvaluationContext<'_> {
+    /// The generation of this context's variables, functions, and options
+    pub fn generation(&self) -> Generation {
+        Generation {
+            vars: self.vars.generations(),
+            funcs: self.funcs.generation(),
+            options: self.options,
+        }
+    }
+}
+
+static NO_VARS: SymbolTable<Variable> = SymbolTable::new();
+static NO_FUNCS: SymbolTable<Function> = SymbolTable::new();
+
+impl Default for EvaluationContext<'_> {
+    fn default() -> Self {
+        Self {
+            vars: Scope::new(&NO_VARS),
+            funcs: &NO_FUNCS,
+            context: ErrorContext::default(),
+            depth: 0,
+            options: Options::default(),
+            budget: Rc::default(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -23,6 +193,7 @@ mod tests {
     use super::{
         errors::{ContextualError, Error, ErrorContext},
         functions::{Function, Functions},
+        number::Number,
         operators::{Associativity, Operator, OperatorType},
         tokens::Token,
         variables::Variable,
@@ -48,6 +219,7 @@ mod tests {
             Function,
             ErrorContext,
             Variable,
+            Number,
             Token,
             Functions,
             &Functions,
@@ -0,0 +1,805 @@
+use std::{convert::TryFrom, fmt};
+
+use num_bigint::BigInt;
+use num_complex::Complex64;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, One, Signed, ToPrimitive, Zero};
+
+use super::{
+    errors::Error,
+    operators::{from_bool, truthy, OperatorType},
+    primes::{self, Interrupt},
+    span::Span,
+    units::Quantity,
+};
+
+/// The largest result, in bits, an exact power or factorial may produce before falling back to floating point
+const MAX_EXACT_BITS: u64 = 1 << 16;
+
+/// The largest integer that is guaranteed to be represented exactly by a float, 2^53
+const MAX_EXACT_FLOAT: f64 = 9_007_199_254_740_992.0;
+
+/// How numeric literals are represented during evaluation
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NumericMode {
+    /// Integers are exact and of arbitrary size, everything else is a 64-bit float
+    #[default]
+    Float,
+    /// Exact rationals. Operators without an exact implementation fall back to floating point.
+    Rational,
+}
+
+/// A numeric value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Number {
+    Float(f64),
+    /// An exact integer of arbitrary size
+    Integer(BigInt),
+    /// An exact integer that is displayed as its prime factorization, e.g. `2^3 × 3^2 × 5`.
+    /// It's otherwise the same as an `Integer`, and never too large to factor
+    Factored(BigInt),
+    /// An exact fraction, never a whole number
+    Rational(BigRational),
+    /// A complex number, never with a zero imaginary part
+    Complex(Complex64),
+    /// A value with a unit, never dimensionless
+    Quantity(Box<Quantity>),
+}
+
+impl Number {
+    /// Create a number from a numeric literal
+    /// * `literal` - The literal's value
+    /// * `mode` - The numeric mode in use
+    pub fn literal(literal: &Literal, mode: NumericMode) -> Self {
+        match (&literal.exact, mode) {
+            (Some(exact), NumericMode::Rational) => Self::exact(exact.clone(), mode),
+            _ => literal.whole().map_or(Self::Float(literal.float), |i| {
+                Self::Integer(i.to_integer())
+            }),
+        }
+    }
+
+    /// Create a number from an exact value, as an integer if it is whole.
+    /// Fractions are only kept exact in rational mode.
+    /// * `value` - The exact value
+    /// * `mode` - The numeric mode in use
+    pub fn exact(value: BigRational, mode: NumericMode) -> Self {
+        if value.is_integer() {
+            Self::Integer(value.to_integer())
+        } else if mode == NumericMode::Rational {
+            Self::Rational(value)
+        } else {
+            Self::Float(value.to_f64().unwrap_or(f64::NAN))
+        }
+    }
+
+    /// Create a number from a complex value, as a float if it has no imaginary part
+    pub fn complex(value: Complex64) -> Self {
+        if value.im == 0.0 {
+            Self::Float(value.re)
+        } else {
+            Self::Complex(value)
+        }
+    }
+
+    /// Create a number from a boolean: 1 for true, 0 for false
+    /// * `exact` - Whether the result should be exact
+    pub fn from_bool(b: bool, exact: bool) -> Self {
+        if exact {
+            Self::Integer(BigInt::from(u8::from(b)))
+        } else {
+            Self::Float(from_bool(b))
+        }
+    }
+
+    /// Convert this number to a 64-bit float, possibly losing precision.
+    /// Complex numbers and quantities are `NaN`
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Self::Float(x) => *x,
+            Self::Integer(i) | Self::Factored(i) => i.to_f64().unwrap_or(f64::NAN),
+            Self::Rational(r) => r.to_f64().unwrap_or(f64::NAN),
+            Self::Complex(_) | Self::Quantity(_) => f64::NAN,
+        }
+    }
+
+    /// Convert this number to a complex number, possibly losing precision
+    pub fn to_complex(&self) -> Complex64 {
+        match self {
+            Self::Complex(c) => *c,
+            real => Complex64::new(real.to_f64(), 0.0),
+        }
+    }
+
+    /// Convert this number to an exact rational, or `None` if it is inexact
+    pub fn to_rational(&self) -> Option<BigRational> {
+        match self {
+            Self::Float(_) | Self::Complex(_) | Self::Quantity(_) => None,
+            Self::Integer(i) | Self::Factored(i) => Some(BigRational::from_integer(i.clone())),
+            Self::Rational(r) => Some(r.clone()),
+        }
+    }
+
+    /// Convert this number to an integer, or `None` if it isn't a real whole number.
+    /// Floats are converted if they're whole, even if they have lost precision
+    pub fn to_integer(&self) -> Option<BigInt> {
+        match self {
+            Self::Integer(i) | Self::Factored(i) => Some(i.clone()),
+            Self::Float(x) if x.fract() == 0.0 => BigInt::from_f64(*x),
+            _ => None,
+        }
+    }
+
+    /// Determines if this number is represented exactly
+    pub const fn is_exact(&self) -> bool {
+        matches!(
+            self,
+            Self::Integer(_) | Self::Factored(_) | Self::Rational(_)
+        )
+    }
+
+    /// Determines if this number is finite, exact numbers always are
+    pub fn is_finite(&self) -> bool {
+        match self {
+            Self::Float(x) => x.is_finite(),
+            Self::Integer(_) | Self::Factored(_) | Self::Rational(_) => true,
+            Self::Complex(c) => c.is_finite(),
+            Self::Quantity(q) => q.value.is_finite(),
+        }
+    }
+
+    /// Determines if this number is `NaN`, or has a `NaN` part
+    pub fn is_nan(&self) -> bool {
+        match self {
+            Self::Float(x) => x.is_nan(),
+            Self::Integer(_) | Self::Factored(_) | Self::Rational(_) => false,
+            Self::Complex(c) => c.is_nan(),
+            Self::Quantity(q) => q.value.is_nan(),
+        }
+    }
+
+    /// Determines if this number is considered to be true: any non-zero number is true
+    pub fn truthy(&self) -> bool {
+        match self {
+            Self::Float(x) => truthy(*x),
+            Self::Integer(i) | Self::Factored(i) => !i.is_zero(),
+            Self::Rational(r) => !r.is_zero(),
+            Self::Complex(c) => !c.is_zero(),
+            Self::Quantity(q) => truthy(q.value),
+        }
+    }
+}
+
+impl From<f64> for Number {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<Complex64> for Number {
+    fn from(value: Complex64) -> Self {
+        Self::complex(value)
+    }
+}
+
+impl From<BigInt> for Number {
+    fn from(value: BigInt) -> Self {
+        Self::Integer(value)
+    }
+}
+
+impl From<BigRational> for Number {
+    fn from(value: BigRational) -> Self {
+        Self::exact(value, NumericMode::Rational)
+    }
+}
+
+/// The value of a numeric literal, as it was written
+#[derive(Debug, Clone, PartialEq)]
+pub struct Literal {
+    /// The exact value, unless it's too large to be worth representing exactly, e.g. `1e100000`
+    exact: Option<BigRational>,
+    /// The nearest float
+    float: f64,
+    /// Whether it was written in scientific notation, e.g. `6.022e23`, which is float syntax
+    scientific: bool,
+}
+
+impl Literal {
+    /// An integer literal of any size, e.g. `0xFFFF_FFFF_FFFF_FFFF_FF`
+    pub fn integer(value: BigInt) -> Self {
+        Self {
+            float: value.to_f64().unwrap_or(f64::INFINITY),
+            exact: Some(BigRational::from_integer(value)),
+            scientific: false,
+        }
+    }
+
+    /// Parse a decimal literal, e.g. `9007199254740993`, `1.5`, or `6.022e23`.
+    /// Digit separators must already have been removed.
+    /// Returns `None` if `digits` isn't a decimal literal
+    pub fn parse(digits: &str) -> Option<Self> {
+        let float = digits.parse::<f64>().ok()?;
+        let (mantissa, exponent) = digits.split_once(['e', 'E']).unwrap_or((digits, ""));
+        let (whole, fract) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+
+        let exact = || {
+            let numer: BigInt = format!("{}{}", whole, fract).parse().ok()?;
+            let exponent = match exponent {
+                "" => 0,
+                exponent => exponent.parse::<i64>().ok()?,
+            } - i64::try_from(fract.len()).ok()?;
+            // 10^n has about 10n/3 bits
+            if exponent.unsigned_abs().saturating_mul(10) / 3 > MAX_EXACT_BITS {
+                return None;
+            }
+            let scale = BigInt::from(10).pow(u32::try_from(exponent.unsigned_abs()).ok()?);
+            Some(if exponent < 0 {
+                BigRational::new(numer, scale)
+            } else {
+                BigRational::from_integer(numer * scale)
+            })
+        };
+        Some(Self {
+            exact: exact(),
+            float,
+            scientific: !exponent.is_empty(),
+        })
+    }
+
+    /// The exact value if it is read as an integer outside of rational mode.
+    /// Literals in scientific notation are floats, so they are only integers while a float holds them exactly
+    fn whole(&self) -> Option<&BigRational> {
+        self.exact.as_ref().filter(|exact| {
+            exact.is_integer() && (!self.scientific || self.float.abs() <= MAX_EXACT_FLOAT)
+        })
+    }
+}
+
+impl From<f64> for Literal {
+    fn from(value: f64) -> Self {
+        Self {
+            exact: decimal(value),
+            float: value,
+            scientific: false,
+        }
+    }
+}
+
+/// Integer literals are written exactly, others as their nearest float
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.whole() {
+            Some(exact) => write!(f, "{}", exact.numer()),
+            None => fmt::Display::fmt(&self.float, f),
+        }
+    }
+}
+
+/// Floats and complex numbers respect the formatter's precision, exact values are always written in full.
+/// Complex numbers are written as `a + bi`, and factored integers as `-1 × 2^2 × 3`
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Float(x) => fmt::Display::fmt(x, f),
+            Self::Integer(i) => write!(f, "{}", i),
+            Self::Factored(i) => write_factored(i, f),
+            Self::Rational(r) => write!(f, "{}/{}", r.numer(), r.denom()),
+            Self::Complex(c) => {
+                let sign = if c.im.is_sign_negative() { '-' } else { '+' };
+                match f.precision() {
+                    Some(p) => write!(f, "{:.*} {} {:.*}i", p, c.re, sign, p, c.im.abs()),
+                    None => write!(f, "{} {} {}i", c.re, sign, c.im.abs()),
+                }
+            }
+            Self::Quantity(q) => fmt::Display::fmt(q, f),
+        }
+    }
+}
+
+/// Write `n` as the product of its prime factors, with `-1` for negative numbers
+fn write_factored(n: &BigInt, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let factors = match primes::factorize_big(n) {
+        Some(factors) if !factors.is_empty() => factors,
+        // Zero, one, and negative one are their own factorization
+        _ => return write!(f, "{}", n),
+    };
+    if n.is_negative() {
+        write!(f, "-1 × ")?;
+    }
+    for (idx, (p, power)) in factors.iter().enumerate() {
+        if idx > 0 {
+            write!(f, " × ")?;
+        }
+        if *power == 1 {
+            write!(f, "{}", p)?;
+        } else {
+            write!(f, "{}^{}", p, power)?;
+        }
+    }
+    Ok(())
+}
+
+/// Convert a float to the rational number written by its shortest decimal representation.
+/// For example, `0.1` becomes `1/10` rather than the exact value of the float.
+/// Returns `None` for infinite and `NaN` values
+fn decimal(value: f64) -> Option<BigRational> {
+    if !value.is_finite() {
+        return None;
+    }
+    // Display for f64 never uses an exponent
+    let string = value.abs().to_string();
+    let (whole, fract) = string.split_once('.').unwrap_or((&string, ""));
+
+    let numer: BigInt = format!("{}{}", whole, fract).parse().ok()?;
+    let denom = BigInt::from(10).pow(u32::try_from(fract.len()).ok()?);
+
+    let r = BigRational::new(numer, denom);
+    Some(if value.is_sign_negative() { -r } else { r })
+}
+
+/// Compute an exact power. Returns `None` if the exponent isn't an integer
+/// or the result would be unreasonably large.
+fn pow(base: &BigRational, exponent: &BigRational) -> Option<BigRational> {
+    if !exponent.is_integer() || (base.is_zero() && exponent.is_negative()) {
+        return None;
+    }
+    let exponent = exponent.to_integer().to_i32()?;
+    let bits = base.numer().bits() + base.denom().bits();
+    if bits.saturating_mul(u64::from(exponent.unsigned_abs())) > MAX_EXACT_BITS {
+        return None;
+    }
+    Some(base.pow(exponent))
+}
+
+/// Compute `x!` exactly. Returns `None` if `x` isn't a non-negative integer
+/// or the result would be unreasonably large.
+fn factorial(x: &BigRational) -> Option<BigRational> {
+    if !x.is_integer() || x.is_negative() {
+        return None;
+    }
+    let n = x.to_integer().to_u64()?;
+    let mut out = BigInt::one();
+    for i in 2..=n {
+        out *= i;
+        if out.bits() > MAX_EXACT_BITS {
+            return None;
+        }
+    }
+    Some(BigRational::from_integer(out))
+}
+
+/// Compute the number of ways to choose `k` of `n` items, where the order matters if `ordered`.
+/// Returns `None` if either is negative or the result would be unreasonably large.
+fn choose(n: &BigInt, k: &BigInt, ordered: bool) -> Option<BigInt> {
+    if n.is_negative() || k.is_negative() {
+        return None;
+    }
+    if k > n {
+        return Some(BigInt::zero());
+    }
+    // nCr(n, k) = nCr(n, n - k)
+    let k = if ordered {
+        k.clone()
+    } else {
+        k.min(&(n - k)).clone()
+    };
+    let mut out = BigInt::one();
+    for i in 0..k.to_u64()? {
+        out *= n - i;
+        // The product of `i + 1` consecutive integers is always divisible by `(i + 1)!`
+        if !ordered {
+            out /= i + 1;
+        }
+        if out.bits() > MAX_EXACT_BITS {
+            return None;
+        }
+    }
+    Some(out)
+}
+
+/// Compute the median of `args`
+fn median(args: &[BigRational]) -> BigRational {
+    let mut sorted = args.to_vec();
+    sorted.sort();
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (&sorted[mid - 1] + &sorted[mid]) / BigInt::from(2)
+    } else {
+        sorted[mid].clone()
+    }
+}
+
+/// Apply an operator to exact arguments.
+/// Returns `None` if the operator has no exact implementation for these arguments,
+/// in which case the caller should fall back to floating point.
+pub(super) fn evaluate_exact(kind: OperatorType, args: &[BigRational]) -> Option<BigRational> {
+    let boolean = |b: bool| Some(BigRational::from_integer(BigInt::from(u8::from(b))));
+    match kind {
+        OperatorType::Add => Some(&args[0] + &args[1]),
+        OperatorType::Sub => Some(&args[0] - &args[1]),
+        OperatorType::Mul | OperatorType::UnitMul => Some(&args[0] * &args[1]),
+        OperatorType::Div if !args[1].is_zero() => Some(&args[0] / &args[1]),
+        // Truncated remainder, the result has the sign of the dividend
+        OperatorType::Mod if !args[1].is_zero() => {
+            Some(&args[0] - &args[1] * (&args[0] / &args[1]).trunc())
+        }
+        OperatorType::IntDiv if !args[1].is_zero() => Some((&args[0] / &args[1]).floor()),
+        // Floored remainder, the result has the sign of the divisor
+        OperatorType::FloorMod if !args[1].is_zero() => {
+            Some(&args[0] - &args[1] * (&args[0] / &args[1]).floor())
+        }
+        // Euclidean remainder, the result is never negative
+        OperatorType::EuclidMod if !args[1].is_zero() => {
+            let divisor = args[1].abs();
+            Some(&args[0] - &divisor * (&args[0] / &divisor).floor())
+        }
+        OperatorType::Pow => pow(&args[0], &args[1]),
+        OperatorType::Factorial => factorial(&args[0]),
+        // Γ(n) = (n - 1)!
+        OperatorType::Gamma if args[0].is_positive() => factorial(&(&args[0] - BigInt::one())),
+        OperatorType::Negative => Some(-&args[0]),
+        OperatorType::Positive | OperatorType::Re | OperatorType::Conj => Some(args[0].clone()),
+        OperatorType::Max => args.iter().max().cloned(),
+        OperatorType::Min => args.iter().min().cloned(),
+        OperatorType::Sum => Some(args.iter().sum()),
+        OperatorType::Product => Some(args.iter().product()),
+        OperatorType::Mean => Some(args.iter().sum::<BigRational>() / BigInt::from(args.len())),
+        OperatorType::Median => Some(median(args)),
+        OperatorType::Abs => Some(args[0].abs()),
+        OperatorType::Floor => Some(args[0].floor()),
+        OperatorType::Ceil => Some(args[0].ceil()),
+        OperatorType::Round => Some(args[0].round()),
+        OperatorType::Trunc => Some(args[0].trunc()),
+        OperatorType::Sign => Some(args[0].signum()),
+        OperatorType::Im => Some(BigRational::zero()),
+        OperatorType::Arg if !args[0].is_negative() => Some(BigRational::zero()),
+        OperatorType::Less => boolean(args[0] < args[1]),
+        OperatorType::Greater => boolean(args[0] > args[1]),
+        OperatorType::LessOrEqual => boolean(args[0] <= args[1]),
+        OperatorType::GreaterOrEqual => boolean(args[0] >= args[1]),
+        OperatorType::Equal => boolean(args[0] == args[1]),
+        OperatorType::NotEqual => boolean(args[0] != args[1]),
+        OperatorType::Not => boolean(args[0].is_zero()),
+        OperatorType::And => boolean(!args[0].is_zero() && !args[1].is_zero()),
+        OperatorType::Or => boolean(!args[0].is_zero() || !args[1].is_zero()),
+        OperatorType::If => Some(
+            if args[0].is_zero() {
+                &args[2]
+            } else {
+                &args[1]
+            }
+            .clone(),
+        ),
+        _ => None,
+    }
+}
+
+/// Apply an operator that only accepts integers, see [`Operator::apply`](super::operators::Operator::apply).
+/// Returns `None` if the operator has no exact result for these arguments,
+/// in which case the caller should fall back to floating point.
+///
+/// ## Errors
+/// Returns an `Error::Domain` for integers too large to test for primality, more than [`primes::MAX_BITS`] bits,
+/// or to factor, `2^64` or more, and the error of `interrupt` if it stops a primality test
+pub(super) fn evaluate_integer(
+    kind: OperatorType,
+    args: &[BigInt],
+    interrupt: Interrupt,
+) -> Result<Option<Number>, Error> {
+    let fold = |f: fn(&BigInt, &BigInt) -> BigInt| {
+        let result = args[1..].iter().fold(args[0].clone(), |acc, x| f(&acc, x));
+        Some(Number::Integer(result))
+    };
+    // The span is filled in from the operator's token during evaluation
+    let domain = || Error::Domain(kind, Span::default());
+    if matches!(kind, OperatorType::IsPrime | OperatorType::NextPrime)
+        && args[0].bits() > primes::MAX_BITS
+    {
+        return Err(domain());
+    }
+    Ok(match kind {
+        OperatorType::Gcd => fold(Integer::gcd),
+        OperatorType::Lcm => fold(Integer::lcm),
+        OperatorType::Choose => choose(&args[0], &args[1], false).map(Number::Integer),
+        OperatorType::Permutations => choose(&args[0], &args[1], true).map(Number::Integer),
+        OperatorType::IsPrime => Some(Number::from_bool(
+            primes::is_prime(&args[0], interrupt)?,
+            true,
+        )),
+        OperatorType::NextPrime => Some(Number::Integer(primes::next_prime(&args[0], interrupt)?)),
+        OperatorType::Factor => {
+            primes::factorize_big(&args[0]).ok_or_else(domain)?;
+            Some(Number::Factored(args[0].clone()))
+        }
+        _ => None,
+    })
+}
+
+/// Compute a complex power, exactly repeated multiplication for integer exponents
+#[allow(clippy::cast_possible_truncation)]
+fn complex_pow(base: Complex64, exponent: Complex64) -> Complex64 {
+    if exponent.im == 0.0 && exponent.re.fract() == 0.0 && exponent.re.abs() <= f64::from(i32::MAX)
+    {
+        base.powi(exponent.re as i32)
+    } else {
+        base.powc(exponent)
+    }
+}
+
+/// Apply an operator to complex arguments.
+/// Returns `None` if the operator has no complex implementation.
+pub(super) fn evaluate_complex(kind: OperatorType, args: &[Complex64]) -> Option<Complex64> {
+    let real = |x: f64| Complex64::new(x, 0.0);
+    Some(match kind {
+        OperatorType::Add => args[0] + args[1],
+        OperatorType::Sub => args[0] - args[1],
+        OperatorType::Mul | OperatorType::UnitMul => args[0] * args[1],
+        OperatorType::Div => args[0] / args[1],
+        OperatorType::Pow => complex_pow(args[0], args[1]),
+        OperatorType::Sqrt => args[0].sqrt(),
+        OperatorType::Cbrt => args[0].cbrt(),
+        OperatorType::Exp => args[0].exp(),
+        OperatorType::Ln => args[0].ln(),
+        OperatorType::Log2 => args[0].log2(),
+        OperatorType::Log10 => args[0].log10(),
+        OperatorType::Sin => args[0].sin(),
+        OperatorType::Cos => args[0].cos(),
+        OperatorType::Tan => args[0].tan(),
+        OperatorType::Asin => args[0].asin(),
+        OperatorType::Acos => args[0].acos(),
+        OperatorType::Atan => args[0].atan(),
+        OperatorType::Sinh => args[0].sinh(),
+        OperatorType::Cosh => args[0].cosh(),
+        OperatorType::Tanh => args[0].tanh(),
+        OperatorType::Negative => -args[0],
+        OperatorType::Positive => args[0],
+        OperatorType::Abs => real(args[0].norm()),
+        OperatorType::Re => real(args[0].re),
+        OperatorType::Im => real(args[0].im),
+        OperatorType::Arg => real(args[0].arg()),
+        OperatorType::Conj => args[0].conj(),
+        OperatorType::Sum => args.iter().sum(),
+        OperatorType::Product => args.iter().product(),
+        #[allow(clippy::cast_precision_loss)]
+        OperatorType::Mean => args.iter().sum::<Complex64>() / args.len() as f64,
+        OperatorType::Equal => real(from_bool(args[0] == args[1])),
+        OperatorType::NotEqual => real(from_bool(args[0] != args[1])),
+        OperatorType::Not => real(from_bool(args[0].is_zero())),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use num_bigint::BigInt;
+    use num_complex::Complex64;
+    use num_rational::BigRational;
+
+    use super::{
+        decimal, evaluate_exact, evaluate_integer, Error, Literal, Number, NumericMode,
+        OperatorType, Span,
+    };
+
+    fn ratio(numer: i64, denom: i64) -> BigRational {
+        BigRational::new(BigInt::from(numer), BigInt::from(denom))
+    }
+
+    #[test]
+    fn test_decimal() {
+        for &(value, numer, denom) in &[
+            (0.1, 1, 10),
+            (-2.5, -5, 2),
+            (3.0, 3, 1),
+            (0.0, 0, 1),
+            (1e-7, 1, 10_000_000),
+        ] {
+            assert_eq!(decimal(value), Some(ratio(numer, denom)));
+        }
+        assert_eq!(decimal(f64::INFINITY), None);
+        assert_eq!(decimal(f64::NAN), None);
+    }
+
+    #[test]
+    fn test_literal() {
+        let literal = |digits: &str| Literal::parse(digits).unwrap();
+        assert_eq!(
+            Number::literal(&literal("0.5"), NumericMode::Float),
+            Number::Float(0.5)
+        );
+        assert_eq!(
+            Number::literal(&literal("0.5"), NumericMode::Rational),
+            Number::Rational(ratio(1, 2))
+        );
+        assert_eq!(
+            Number::literal(&literal("1e-3"), NumericMode::Rational),
+            Number::Rational(ratio(1, 1000))
+        );
+        for &mode in &[NumericMode::Float, NumericMode::Rational] {
+            for &(digits, expected) in &[
+                ("12", "12"),
+                ("1.5e1", "15"),
+                ("1e15", "1000000000000000"),
+                ("9007199254740993", "9007199254740993"),
+            ] {
+                assert_eq!(
+                    Number::literal(&literal(digits), mode),
+                    Number::Integer(expected.parse().unwrap()),
+                    "Checking [{}]",
+                    digits
+                );
+            }
+            let wide = Literal::integer(BigInt::from(u128::MAX) * 256);
+            assert_eq!(
+                Number::literal(&wide, mode),
+                Number::Integer(BigInt::from(u128::MAX) * 256)
+            );
+            // Too large to be worth representing exactly
+            assert_eq!(
+                Number::literal(&literal("1e100000"), mode),
+                Number::Float(f64::INFINITY)
+            );
+        }
+
+        // Scientific notation is float syntax, only exact in rational mode
+        assert_eq!(
+            Number::literal(&literal("6.022e23"), NumericMode::Float),
+            Number::Float(6.022e23)
+        );
+        assert_eq!(
+            Number::literal(&literal("1e400"), NumericMode::Float),
+            Number::Float(f64::INFINITY)
+        );
+        assert_eq!(
+            Number::literal(&literal("6.022e23"), NumericMode::Rational),
+            Number::Integer("602200000000000000000000".parse().unwrap())
+        );
+        assert_eq!(
+            Number::literal(&literal("1e400"), NumericMode::Rational),
+            Number::Integer(BigInt::from(10).pow(400))
+        );
+        assert_eq!(literal("6.022e23").to_string(), 6.022e23.to_string());
+
+        assert_eq!(Literal::parse("1.2.3"), None);
+        assert_eq!(literal("9007199254740993").to_string(), "9007199254740993");
+        assert_eq!(literal("1.5").to_string(), "1.5");
+        assert_eq!(Literal::from(1.5), literal("1.5"));
+    }
+
+    #[test]
+    fn test_exact_constructor() {
+        assert_eq!(
+            Number::exact(ratio(6, 3), NumericMode::Float),
+            Number::Integer(BigInt::from(2))
+        );
+        assert_eq!(
+            Number::exact(ratio(1, 2), NumericMode::Float),
+            Number::Float(0.5)
+        );
+        assert_eq!(
+            Number::exact(ratio(1, 2), NumericMode::Rational),
+            Number::Rational(ratio(1, 2))
+        );
+    }
+
+    #[test]
+    fn test_exact() {
+        let third = ratio(1, 3);
+        assert_eq!(
+            evaluate_exact(OperatorType::Mul, &[third.clone(), ratio(3, 1)]),
+            Some(ratio(1, 1))
+        );
+        assert_eq!(
+            evaluate_exact(OperatorType::Div, &[third.clone(), ratio(0, 1)]),
+            None
+        );
+        assert_eq!(
+            evaluate_exact(OperatorType::Pow, &[ratio(2, 3), ratio(-2, 1)]),
+            Some(ratio(9, 4))
+        );
+        assert_eq!(
+            evaluate_exact(OperatorType::Pow, &[ratio(2, 1), third.clone()]),
+            None
+        );
+        assert_eq!(
+            evaluate_exact(OperatorType::Mod, &[ratio(-7, 2), ratio(1, 1)]),
+            Some(ratio(-1, 2))
+        );
+        assert_eq!(
+            evaluate_exact(OperatorType::Mean, &[third, ratio(1, 6)]),
+            Some(ratio(1, 4))
+        );
+        assert_eq!(
+            evaluate_exact(OperatorType::FloorMod, &[ratio(-7, 1), ratio(3, 1)]),
+            Some(ratio(2, 1))
+        );
+        assert_eq!(
+            evaluate_exact(OperatorType::EuclidMod, &[ratio(7, 1), ratio(-3, 1)]),
+            Some(ratio(1, 1))
+        );
+        assert_eq!(
+            evaluate_exact(OperatorType::IntDiv, &[ratio(-7, 2), ratio(1, 1)]),
+            Some(ratio(-4, 1))
+        );
+        assert_eq!(evaluate_exact(OperatorType::Sin, &[ratio(1, 1)]), None);
+        assert_eq!(
+            evaluate_exact(OperatorType::Factorial, &[ratio(5, 1)]),
+            Some(ratio(120, 1))
+        );
+        assert_eq!(
+            evaluate_exact(OperatorType::Factorial, &[ratio(-1, 1)]),
+            None
+        );
+        assert_eq!(
+            evaluate_exact(OperatorType::Factorial, &[ratio(100_000, 1)]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_integer() {
+        let integers = |args: &[i64]| args.iter().map(|&i| BigInt::from(i)).collect::<Vec<_>>();
+        let integer = |i: i64| Some(Number::Integer(BigInt::from(i)));
+        for &(kind, ref args, ref expected) in &[
+            (OperatorType::Gcd, integers(&[12, -18, 27]), integer(3)),
+            (OperatorType::Lcm, integers(&[4, 6]), integer(12)),
+            (OperatorType::Choose, integers(&[10, 3]), integer(120)),
+            (OperatorType::Choose, integers(&[3, 10]), integer(0)),
+            (OperatorType::Choose, integers(&[-3, 1]), None),
+            (OperatorType::Permutations, integers(&[10, 3]), integer(720)),
+            (OperatorType::Choose, integers(&[1_000_000, 500_000]), None),
+            (OperatorType::IsPrime, integers(&[91]), integer(0)),
+            (OperatorType::NextPrime, integers(&[89]), integer(97)),
+            (
+                OperatorType::Factor,
+                integers(&[-360]),
+                Some(Number::Factored(BigInt::from(-360))),
+            ),
+        ] {
+            assert_eq!(
+                &evaluate_integer(kind, args, &|| Ok(())).unwrap(),
+                expected,
+                "Checking {:?}",
+                kind
+            );
+        }
+
+        // Too large to factor or test for primality
+        let domain = |kind| Err(Error::Domain(kind, Span::default()));
+        let big: BigInt = BigInt::from(u64::MAX) + 1;
+        assert_eq!(
+            evaluate_integer(OperatorType::Factor, std::slice::from_ref(&big), &|| Ok(())),
+            domain(OperatorType::Factor)
+        );
+        assert!(evaluate_integer(OperatorType::IsPrime, &[big], &|| Ok(())).is_ok());
+        let huge = BigInt::from(1) << 1024_u32;
+        for kind in [OperatorType::IsPrime, OperatorType::NextPrime] {
+            assert_eq!(
+                evaluate_integer(kind, std::slice::from_ref(&huge), &|| Ok(())),
+                domain(kind)
+            );
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Number::Rational(ratio(-3, 4)).to_string(), "-3/4");
+        assert_eq!(Number::from(ratio(6, 3)).to_string(), "2");
+        assert_eq!(Number::Integer(BigInt::from(-42)).to_string(), "-42");
+        assert_eq!(
+            Number::Factored(BigInt::from(-360)).to_string(),
+            "-1 × 2^3 × 3^2 × 5"
+        );
+        assert_eq!(Number::Factored(BigInt::from(7)).to_string(), "7");
+        assert_eq!(Number::Factored(BigInt::from(1)).to_string(), "1");
+        assert_eq!(format!("{:.3}", Number::Float(0.5)), "0.500");
+        assert_eq!(format!("{:.3}", Number::Rational(ratio(1, 2))), "1/2");
+        assert_eq!(
+            format!("{:.2}", Number::from(Complex64::new(1.0, -0.5))),
+            "1.00 - 0.50i"
+        );
+        assert_eq!(Number::from(Complex64::new(2.0, 0.0)), Number::Float(2.0));
+    }
+}
@@ -2,11 +2,20 @@
 
 use std::fmt;
 
+use num_bigint::BigInt;
+use num_complex::Complex64;
+use num_rational::BigRational;
 use rand::Rng;
 
-use super::representable::{get_by_repr, Representable};
+use super::{
+    errors::Error,
+    number::{self, Number},
+    representable::{get_longest_by_repr, Representable},
+    span::Span,
+    special, units, Budget, Options,
+};
 
-#[derive(PartialEq, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum OperatorType {
     Add,
     Sub,
@@ -14,15 +23,69 @@ pub enum OperatorType {
     Div,
     Pow,
     Mod,
+    IntDiv,
+    FloorMod,
+    EuclidMod,
+    UnitMul,
+    Convert,
     Sin,
     Cos,
     Tan,
     Max,
     Min,
+    Sum,
+    Mean,
+    Median,
+    Product,
+    Gcd,
+    Lcm,
+    Choose,
+    Permutations,
+    IsPrime,
+    NextPrime,
+    Factor,
     Sqrt,
+    Cbrt,
+    Ln,
+    Log2,
+    Log10,
+    Exp,
+    Asin,
+    Acos,
+    Atan,
+    Atan2,
+    Sinh,
+    Cosh,
+    Tanh,
+    Abs,
+    Floor,
+    Ceil,
+    Round,
+    Trunc,
+    Sign,
+    Re,
+    Im,
+    Arg,
+    Conj,
+    Hypot,
+    Gamma,
+    Lgamma,
+    Beta,
+    Erf,
+    Erfc,
     Negative,
     Positive,
     Factorial,
+    Less,
+    Greater,
+    LessOrEqual,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+    Not,
+    If,
     RandomInt,
     RandomFloat,
 }
@@ -35,29 +98,88 @@ pub const FUNCTIONAL_STYLE_OPERATORS: &[OperatorType] = &[
     OperatorType::Tan,
     OperatorType::Max,
     OperatorType::Min,
+    OperatorType::Sum,
+    OperatorType::Mean,
+    OperatorType::Median,
+    OperatorType::Product,
+    OperatorType::Gcd,
+    OperatorType::Lcm,
+    OperatorType::Choose,
+    OperatorType::Permutations,
+    OperatorType::IsPrime,
+    OperatorType::NextPrime,
+    OperatorType::Factor,
+    OperatorType::Ln,
+    OperatorType::Log2,
+    OperatorType::Log10,
+    OperatorType::Exp,
+    OperatorType::Asin,
+    OperatorType::Acos,
+    OperatorType::Atan,
+    OperatorType::Atan2,
+    OperatorType::Sinh,
+    OperatorType::Cosh,
+    OperatorType::Tanh,
+    OperatorType::Abs,
+    OperatorType::Floor,
+    OperatorType::Ceil,
+    OperatorType::Round,
+    OperatorType::Trunc,
+    OperatorType::Sign,
+    OperatorType::Re,
+    OperatorType::Im,
+    OperatorType::Arg,
+    OperatorType::Conj,
+    OperatorType::Hypot,
+    OperatorType::Gamma,
+    OperatorType::Lgamma,
+    OperatorType::Beta,
+    OperatorType::Erf,
+    OperatorType::Erfc,
+    OperatorType::If,
     OperatorType::RandomFloat,
     OperatorType::RandomInt,
 ];
 
+/// Operators that only accept integers, see [`Operator::apply`]
+pub const INTEGER_OPERATORS: &[OperatorType] = &[
+    OperatorType::Gcd,
+    OperatorType::Lcm,
+    OperatorType::Choose,
+    OperatorType::Permutations,
+    OperatorType::IsPrime,
+    OperatorType::NextPrime,
+    OperatorType::Factor,
+];
+
 impl Representable for OperatorType {
     fn repr(&self) -> &'static [&'static str] {
         Operator::by_type(*self).repr
     }
 }
 
-#[derive(Clone, PartialEq, Copy, Debug)]
+#[derive(Clone, PartialEq, Eq, Copy, Debug)]
 pub enum Associativity {
     Left,
     Right,
 }
 
+/// The number of arguments an operator accepts
+#[derive(Clone, PartialEq, Eq, Copy, Debug)]
+pub enum Arity {
+    /// Exactly this many arguments
+    Fixed(usize),
+    /// One or more arguments, determined by the commas inside the call's parentheses
+    Variadic,
+}
+
 #[derive(Clone, Copy)]
 pub struct Operator {
     pub kind: OperatorType,
     pub repr: &'static [&'static str],
     pub precedence: u8,
     pub associativity: Associativity,
-    pub arity: usize,
+    pub arity: Arity,
     pub doit: fn(&[f64]) -> f64,
 }
 
@@ -81,14 +203,20 @@ impl Representable for Operator {
 }
 
 impl Operator {
+    /// Every builtin `Operator`
+    pub fn all() -> &'static [Self] {
+        OPERATORS
+    }
+
     /// Get an `Operator` by its `OperatorType`
     pub fn by_type(kind: OperatorType) -> &'static Self {
         OPERATORS.iter().find(|op| op.kind == kind).unwrap()
     }
 
-    /// get an `Operator` by one of its string representations
+    /// get an `Operator` by one of its string representations.
+    /// Alphabetic names must be whole words, e.g. `re` doesn't match the start of `rem`
     pub fn by_repr(repr: &str) -> Option<(&'static Self, usize)> {
-        get_by_repr(repr, OPERATORS)
+        get_longest_by_repr(repr, OPERATORS, true)
     }
 
     /// Determines if the next sequence is an `Operator`
@@ -98,12 +226,127 @@ impl Operator {
 
     // Determines if the next sequence is a unary `Operator`
     pub fn unary(repr: &str) -> Option<(&OperatorType, usize)> {
-        get_by_repr(repr, UNARY_OPERATORS)
+        get_longest_by_repr(repr, UNARY_OPERATORS, true)
+    }
+
+    /// Apply this operator to a list of arguments.
+    /// The result is exact if every argument is exact and the operator supports it,
+    /// complex if any argument is complex, otherwise the arguments are converted to floats.
+    /// Arguments with units are handled separately, see [`units::evaluate`]
+    /// * `options` - The numeric mode determines if fractional results are kept exact,
+    ///   and strict mode checks the result
+    /// * `budget` - Long-running operators, like `nextprime`, stop when it runs out of time
+    ///
+    /// ## Errors
+    /// Returns an `Error::Dimension` if the arguments have incompatible units,
+    /// an `Error::NotInteger` if an integer operator is given anything else,
+    /// and an `Error::Timeout` if the operator runs out of time.
+    /// In strict mode, returns an `Error::DivisionByZero`, `Error::Domain`, or `Error::Overflow`
+    /// rather than a result that is `NaN`, infinite, or complex from real arguments.
+    pub fn apply(
+        &self,
+        args: &[Number],
+        options: Options,
+        budget: &Budget,
+    ) -> Result<Number, Error> {
+        let result = self.compute(args, options, budget)?;
+        if options.strict {
+            self.check_strict(args, &result)?;
+        }
+        Ok(result)
+    }
+
+    /// Determines if the result of this operator is well-defined, see [`Operator::apply`]
+    fn check_strict(&self, args: &[Number], result: &Number) -> Result<(), Error> {
+        let negative = |arg: &Number| arg.to_f64() < 0.0;
+        let by_zero = match self.kind {
+            OperatorType::Div
+            | OperatorType::Mod
+            | OperatorType::IntDiv
+            | OperatorType::FloorMod
+            | OperatorType::EuclidMod => !args[1].truthy(),
+            OperatorType::Pow => !args[0].truthy() && negative(&args[1]),
+            _ => false,
+        };
+        if by_zero {
+            return Err(Error::DivisionByZero(Span::default()));
+        }
+
+        // `NaN` and infinite arguments can only be passed on
+        if !args.iter().all(Number::is_finite) {
+            return Ok(());
+        }
+        // Poles, where the result diverges, are outside of the domain rather than an overflow, e.g. `ln(0)`
+        let pole = match self.kind {
+            OperatorType::Ln | OperatorType::Log2 | OperatorType::Log10 => !args[0].truthy(),
+            OperatorType::Lgamma => {
+                let x = args[0].to_f64();
+                x <= 0.0 && x.fract() == 0.0
+            }
+            _ => false,
+        };
+        let real = |arg: &Number| !matches!(arg, Number::Complex(_));
+        let outside_domain = pole || result.is_nan() || (args.iter().all(real) && !real(result));
+        if outside_domain {
+            Err(Error::Domain(self.kind, Span::default()))
+        } else if !result.is_finite() {
+            Err(Error::Overflow(self.kind, Span::default()))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn compute(&self, args: &[Number], options: Options, budget: &Budget) -> Result<Number, Error> {
+        let mode = options.numeric;
+        if self.kind == OperatorType::Convert
+            || args.iter().any(|arg| matches!(arg, Number::Quantity(_)))
+        {
+            return units::evaluate(self, args);
+        }
+
+        if INTEGER_OPERATORS.contains(&self.kind) {
+            let integers: Vec<BigInt> = args
+                .iter()
+                .map(Number::to_integer)
+                .collect::<Option<_>>()
+                .ok_or_else(|| Error::NotInteger(self.kind, Span::default()))?;
+            let interrupt = || budget.check_time(&options.limits);
+            if let Some(result) = number::evaluate_integer(self.kind, &integers, &interrupt)? {
+                return Ok(result);
+            }
+        }
+
+        let rationals: Option<Vec<BigRational>> = args.iter().map(Number::to_rational).collect();
+        if let Some(result) = rationals.and_then(|args| number::evaluate_exact(self.kind, &args)) {
+            return Ok(Number::exact(result, mode));
+        }
+
+        // Complex numbers aren't ordered, so operators like `max` and `<` are undefined for them
+        if args.iter().any(|arg| matches!(arg, Number::Complex(_))) {
+            let args: Vec<Complex64> = args.iter().map(Number::to_complex).collect();
+            return number::evaluate_complex(self.kind, &args)
+                .map(Number::complex)
+                .ok_or_else(|| Error::Domain(self.kind, Span::default()));
+        }
+
+        let args: Vec<f64> = args.iter().map(Number::to_f64).collect();
+        let result = (self.doit)(&args);
+
+        // Real arguments outside of the operator's real domain may still have a complex result, e.g. `sqrt(-1)`
+        if result.is_nan() && !args.iter().any(|arg| arg.is_nan()) {
+            let args: Vec<Complex64> = args.iter().map(|&arg| Complex64::new(arg, 0.0)).collect();
+            if let Some(result) =
+                number::evaluate_complex(self.kind, &args).filter(|result| !result.is_nan())
+            {
+                return Ok(Number::complex(result));
+            }
+        }
+        Ok(Number::Float(result))
     }
 }
 
 #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
-fn _factorial(x: f64) -> f64 {
+fn factorial_loop(x: f64) -> f64 {
     let mut out: f64 = 1.0;
     for i in 1..=(x as i64) {
         out *= i as f64;
@@ -111,12 +354,105 @@ fn _factorial(x: f64) -> f64 {
     out
 }
 
-/// Compute `x!`
+/// Convert a boolean into a number: 1 for true, 0 for false
+pub const fn from_bool(b: bool) -> f64 {
+    if b {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// Determines if a number is considered to be true: any non-zero number is true
+pub fn truthy(x: f64) -> bool {
+    x != 0.0
+}
+
+/// Compute the median of `arr`
+fn median(arr: &[f64]) -> f64 {
+    let mut sorted = arr.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        f64::midpoint(sorted[mid - 1], sorted[mid])
+    } else {
+        sorted[mid]
+    }
+}
+
+/// Compute the greatest common divisor of two integers.
+/// Returns `NaN` if either is not an integer
+#[allow(clippy::while_float)]
+fn gcd(a: f64, b: f64) -> f64 {
+    if a.fract() != 0.0 || b.fract() != 0.0 {
+        return f64::NAN;
+    }
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0.0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Compute the least common multiple of two integers.
+/// Returns `NaN` if either is not an integer
+fn lcm(a: f64, b: f64) -> f64 {
+    let divisor = gcd(a, b);
+    if divisor == 0.0 {
+        // Only possible when both are zero
+        0.0
+    } else {
+        (a / divisor * b).abs()
+    }
+}
+
+/// Compute the floored remainder of `a / b`, which has the sign of `b`
+fn floor_mod(a: f64, b: f64) -> f64 {
+    let remainder = a % b;
+    if remainder != 0.0 && (remainder < 0.0) != (b < 0.0) {
+        remainder + b
+    } else {
+        remainder
+    }
+}
+
+/// Compute the number of ways to choose `k` of `n` items, where the order matters if `ordered`.
+/// Returns `NaN` if either is negative
+fn choose(n: f64, k: f64, ordered: bool) -> f64 {
+    if n < 0.0 || k < 0.0 {
+        f64::NAN
+    } else if k > n {
+        0.0
+    } else {
+        let unordered = if ordered {
+            0.0
+        } else {
+            special::lgamma(k + 1.0)
+        };
+        (special::lgamma(n + 1.0) - special::lgamma(n - k + 1.0) - unordered)
+            .exp()
+            .round()
+    }
+}
+
+/// Compute the sign of `x`: -1, 0, or 1
+fn sign(x: f64) -> f64 {
+    if x == 0.0 {
+        0.0
+    } else {
+        x.signum()
+    }
+}
+
+/// Compute `x!`, using the gamma function for non-integers.
+/// Returns `NaN` for negative integers
 fn factorial(x: f64) -> f64 {
     if x >= 1000.0 {
         f64::INFINITY
+    } else if x.fract() == 0.0 && x >= 0.0 {
+        factorial_loop(x)
     } else {
-        _factorial(x.floor())
+        special::gamma(x + 1.0)
     }
 }
 
@@ -125,137 +461,579 @@ static OPERATORS: &[Operator] = &[
     Operator {
         kind: OperatorType::Add,
         repr: &["+", "add", "plus"],
-        precedence: 1,
+        precedence: 5,
         associativity: Associativity::Left,
-        arity: 2,
+        arity: Arity::Fixed(2),
         doit: |arr| arr[0] + arr[1],
     },
     Operator {
         kind: OperatorType::Sub,
         repr: &["-", "subtract", "sub", "minus"],
-        precedence: 1,
+        precedence: 5,
         associativity: Associativity::Left,
-        arity: 2,
+        arity: Arity::Fixed(2),
         doit: |arr| arr[0] - arr[1],
     },
     Operator {
         kind: OperatorType::Mul,
         repr: &["×", "*", "times", "⋅", "mul"],
-        precedence: 2,
+        precedence: 6,
         associativity: Associativity::Left,
-        arity: 2,
+        arity: Arity::Fixed(2),
         doit: |arr| arr[0] * arr[1],
     },
     Operator {
         kind: OperatorType::Div,
         repr: &["÷", "/", "over", "divide", "div"],
-        precedence: 2,
+        precedence: 6,
         associativity: Associativity::Left,
-        arity: 2,
+        arity: Arity::Fixed(2),
         doit: |arr| arr[0] / arr[1],
     },
     Operator {
         kind: OperatorType::Pow,
-        repr: &["^", "exp", "pow"],
-        precedence: 4,
+        repr: &["^", "pow"],
+        precedence: 8,
         associativity: Associativity::Right,
-        arity: 2,
+        arity: Arity::Fixed(2),
         doit: |arr| arr[0].powf(arr[1]),
     },
     Operator {
         kind: OperatorType::Mod,
         repr: &["%", "mod"],
-        precedence: 3,
+        precedence: 7,
         associativity: Associativity::Left,
-        arity: 2,
+        arity: Arity::Fixed(2),
         doit: |arr| arr[0] % arr[1],
     },
+    Operator {
+        kind: OperatorType::IntDiv,
+        repr: &["//"],
+        precedence: 6,
+        associativity: Associativity::Left,
+        arity: Arity::Fixed(2),
+        doit: |arr| (arr[0] / arr[1]).floor(),
+    },
+    Operator {
+        kind: OperatorType::FloorMod,
+        repr: &["%%", "floormod"],
+        precedence: 7,
+        associativity: Associativity::Left,
+        arity: Arity::Fixed(2),
+        doit: |arr| floor_mod(arr[0], arr[1]),
+    },
+    Operator {
+        kind: OperatorType::EuclidMod,
+        repr: &["euclidmod"],
+        precedence: 7,
+        associativity: Associativity::Left,
+        arity: Arity::Fixed(2),
+        doit: |arr| arr[0].rem_euclid(arr[1]),
+    },
+    // Inserted between a value and its unit, e.g. `5 m / 2 s` => `5·m / 2·s`
+    // It binds tighter than the other arithmetic operators so that the unit stays with its value
+    Operator {
+        kind: OperatorType::UnitMul,
+        repr: &["·"],
+        precedence: 7,
+        associativity: Associativity::Left,
+        arity: Arity::Fixed(2),
+        doit: |arr| arr[0] * arr[1],
+    },
+    // Only defined for quantities, e.g. `12 in to cm`
+    Operator {
+        kind: OperatorType::Convert,
+        repr: &["to"],
+        precedence: 0,
+        associativity: Associativity::Left,
+        arity: Arity::Fixed(2),
+        doit: |_| f64::NAN,
+    },
     Operator {
         kind: OperatorType::Sin,
         repr: &["sin"],
-        precedence: 4,
+        precedence: 8,
         associativity: Associativity::Right,
-        arity: 1,
+        arity: Arity::Fixed(1),
         doit: |arr| arr[0].sin(),
     },
     Operator {
         kind: OperatorType::Cos,
         repr: &["cos"],
-        precedence: 4,
+        precedence: 8,
         associativity: Associativity::Right,
-        arity: 1,
+        arity: Arity::Fixed(1),
         doit: |arr| arr[0].cos(),
     },
     Operator {
         kind: OperatorType::Tan,
         repr: &["tan"],
-        precedence: 4,
+        precedence: 8,
         associativity: Associativity::Right,
-        arity: 1,
+        arity: Arity::Fixed(1),
         doit: |arr| arr[0].tan(),
     },
     Operator {
         kind: OperatorType::Max,
         repr: &["max"],
-        precedence: 4,
+        precedence: 8,
         associativity: Associativity::Right,
-        arity: 2,
-        doit: |arr| arr[0].max(arr[1]),
+        arity: Arity::Variadic,
+        doit: |arr| arr.iter().copied().fold(f64::NEG_INFINITY, f64::max),
     },
     Operator {
         kind: OperatorType::Min,
         repr: &["min"],
-        precedence: 4,
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Variadic,
+        doit: |arr| arr.iter().copied().fold(f64::INFINITY, f64::min),
+    },
+    Operator {
+        kind: OperatorType::Sum,
+        repr: &["sum"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Variadic,
+        doit: |arr| arr.iter().sum(),
+    },
+    Operator {
+        kind: OperatorType::Mean,
+        repr: &["mean", "avg", "average"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Variadic,
+        doit: |arr| arr.iter().sum::<f64>() / arr.len() as f64,
+    },
+    Operator {
+        kind: OperatorType::Median,
+        repr: &["median"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Variadic,
+        doit: median,
+    },
+    Operator {
+        kind: OperatorType::Product,
+        repr: &["product", "prod"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Variadic,
+        doit: |arr| arr.iter().product(),
+    },
+    Operator {
+        kind: OperatorType::Gcd,
+        repr: &["gcd"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Variadic,
+        doit: |arr| arr[1..].iter().fold(arr[0], |acc, x| gcd(acc, *x)),
+    },
+    Operator {
+        kind: OperatorType::Lcm,
+        repr: &["lcm"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Variadic,
+        doit: |arr| arr[1..].iter().fold(arr[0], |acc, x| lcm(acc, *x)),
+    },
+    // The integer operators are computed exactly, see `number::evaluate_integer`
+    // `doit` is only used for results too large to be exact
+    Operator {
+        kind: OperatorType::Choose,
+        repr: &["nCr", "choose", "binomial"],
+        precedence: 8,
         associativity: Associativity::Right,
-        arity: 2,
-        doit: |arr| arr[0].min(arr[1]),
+        arity: Arity::Fixed(2),
+        doit: |arr| choose(arr[0], arr[1], false),
+    },
+    Operator {
+        kind: OperatorType::Permutations,
+        repr: &["nPr", "permutations"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(2),
+        doit: |arr| choose(arr[0], arr[1], true),
+    },
+    Operator {
+        kind: OperatorType::IsPrime,
+        repr: &["isprime"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |_| f64::NAN,
+    },
+    Operator {
+        kind: OperatorType::NextPrime,
+        repr: &["nextprime"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |_| f64::NAN,
+    },
+    // Integers too large to factor have no result
+    Operator {
+        kind: OperatorType::Factor,
+        repr: &["factor"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |_| f64::NAN,
     },
     Operator {
         kind: OperatorType::Sqrt,
         repr: &["√", "sqrt", "root"],
-        precedence: 4,
+        precedence: 8,
         associativity: Associativity::Right,
-        arity: 1,
+        arity: Arity::Fixed(1),
         doit: |arr| arr[0].sqrt(),
     },
+    Operator {
+        kind: OperatorType::Cbrt,
+        repr: &["∛", "cbrt"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| arr[0].cbrt(),
+    },
+    Operator {
+        kind: OperatorType::Ln,
+        repr: &["ln"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| arr[0].ln(),
+    },
+    Operator {
+        kind: OperatorType::Log2,
+        repr: &["log2"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| arr[0].log2(),
+    },
+    Operator {
+        kind: OperatorType::Log10,
+        repr: &["log10", "log"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| arr[0].log10(),
+    },
+    Operator {
+        kind: OperatorType::Exp,
+        repr: &["exp"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| arr[0].exp(),
+    },
+    Operator {
+        kind: OperatorType::Asin,
+        repr: &["asin", "arcsin"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| arr[0].asin(),
+    },
+    Operator {
+        kind: OperatorType::Acos,
+        repr: &["acos", "arccos"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| arr[0].acos(),
+    },
+    Operator {
+        kind: OperatorType::Atan,
+        repr: &["atan", "arctan"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| arr[0].atan(),
+    },
+    Operator {
+        kind: OperatorType::Atan2,
+        repr: &["atan2", "arctan2"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(2),
+        doit: |arr| arr[0].atan2(arr[1]),
+    },
+    Operator {
+        kind: OperatorType::Sinh,
+        repr: &["sinh"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| arr[0].sinh(),
+    },
+    Operator {
+        kind: OperatorType::Cosh,
+        repr: &["cosh"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| arr[0].cosh(),
+    },
+    Operator {
+        kind: OperatorType::Tanh,
+        repr: &["tanh"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| arr[0].tanh(),
+    },
+    Operator {
+        kind: OperatorType::Abs,
+        repr: &["abs"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| arr[0].abs(),
+    },
+    Operator {
+        kind: OperatorType::Floor,
+        repr: &["floor"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| arr[0].floor(),
+    },
+    Operator {
+        kind: OperatorType::Ceil,
+        repr: &["ceil", "ceiling"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| arr[0].ceil(),
+    },
+    Operator {
+        kind: OperatorType::Round,
+        repr: &["round"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| arr[0].round(),
+    },
+    Operator {
+        kind: OperatorType::Trunc,
+        repr: &["trunc", "truncate"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| arr[0].trunc(),
+    },
+    Operator {
+        kind: OperatorType::Sign,
+        repr: &["sign", "sgn"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| sign(arr[0]),
+    },
+    Operator {
+        kind: OperatorType::Re,
+        repr: &["re", "real"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| arr[0],
+    },
+    Operator {
+        kind: OperatorType::Im,
+        repr: &["im", "imag"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |_| 0.0,
+    },
+    Operator {
+        kind: OperatorType::Arg,
+        repr: &["arg", "phase"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| 0.0_f64.atan2(arr[0]),
+    },
+    Operator {
+        kind: OperatorType::Conj,
+        repr: &["conj"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| arr[0],
+    },
+    Operator {
+        kind: OperatorType::Hypot,
+        repr: &["hypot"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(2),
+        doit: |arr| arr[0].hypot(arr[1]),
+    },
+    Operator {
+        kind: OperatorType::Gamma,
+        repr: &["Γ", "gamma"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| special::gamma(arr[0]),
+    },
+    Operator {
+        kind: OperatorType::Lgamma,
+        repr: &["lgamma", "lnΓ", "lngamma"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| special::lgamma(arr[0]),
+    },
+    Operator {
+        kind: OperatorType::Beta,
+        repr: &["beta"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(2),
+        doit: |arr| special::beta(arr[0], arr[1]),
+    },
+    Operator {
+        kind: OperatorType::Erf,
+        repr: &["erf"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| special::erf(arr[0]),
+    },
+    Operator {
+        kind: OperatorType::Erfc,
+        repr: &["erfc"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| special::erfc(arr[0]),
+    },
     Operator {
         kind: OperatorType::Factorial,
         repr: &["!", "factorial", "fact"],
-        precedence: 4,
+        precedence: 8,
         associativity: Associativity::Left,
-        arity: 1,
+        arity: Arity::Fixed(1),
         doit: |arr| factorial(arr[0]),
     },
+    Operator {
+        kind: OperatorType::Less,
+        repr: &["<"],
+        precedence: 4,
+        associativity: Associativity::Left,
+        arity: Arity::Fixed(2),
+        doit: |arr| from_bool(arr[0] < arr[1]),
+    },
+    Operator {
+        kind: OperatorType::Greater,
+        repr: &[">"],
+        precedence: 4,
+        associativity: Associativity::Left,
+        arity: Arity::Fixed(2),
+        doit: |arr| from_bool(arr[0] > arr[1]),
+    },
+    Operator {
+        kind: OperatorType::LessOrEqual,
+        repr: &["<=", "≤"],
+        precedence: 4,
+        associativity: Associativity::Left,
+        arity: Arity::Fixed(2),
+        doit: |arr| from_bool(arr[0] <= arr[1]),
+    },
+    Operator {
+        kind: OperatorType::GreaterOrEqual,
+        repr: &[">=", "≥"],
+        precedence: 4,
+        associativity: Associativity::Left,
+        arity: Arity::Fixed(2),
+        doit: |arr| from_bool(arr[0] >= arr[1]),
+    },
+    Operator {
+        kind: OperatorType::Equal,
+        repr: &["=="],
+        precedence: 4,
+        associativity: Associativity::Left,
+        arity: Arity::Fixed(2),
+        #[allow(clippy::float_cmp)]
+        doit: |arr| from_bool(arr[0] == arr[1]),
+    },
+    Operator {
+        kind: OperatorType::NotEqual,
+        repr: &["!=", "≠"],
+        precedence: 4,
+        associativity: Associativity::Left,
+        arity: Arity::Fixed(2),
+        #[allow(clippy::float_cmp)]
+        doit: |arr| from_bool(arr[0] != arr[1]),
+    },
+    Operator {
+        kind: OperatorType::Not,
+        repr: &["not", "¬"],
+        precedence: 3,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(1),
+        doit: |arr| from_bool(!truthy(arr[0])),
+    },
+    Operator {
+        kind: OperatorType::And,
+        repr: &["&&", "and"],
+        precedence: 2,
+        associativity: Associativity::Left,
+        arity: Arity::Fixed(2),
+        doit: |arr| from_bool(truthy(arr[0]) && truthy(arr[1])),
+    },
+    Operator {
+        kind: OperatorType::Or,
+        repr: &["||", "or"],
+        precedence: 1,
+        associativity: Associativity::Left,
+        arity: Arity::Fixed(2),
+        doit: |arr| from_bool(truthy(arr[0]) || truthy(arr[1])),
+    },
+    // `if` is evaluated lazily, only one of its branches is ever evaluated
+    // `doit` is the equivalent eager computation
+    Operator {
+        kind: OperatorType::If,
+        repr: &["if"],
+        precedence: 8,
+        associativity: Associativity::Right,
+        arity: Arity::Fixed(3),
+        doit: |arr| if truthy(arr[0]) { arr[1] } else { arr[2] },
+    },
     Operator {
         kind: OperatorType::RandomFloat,
         repr: &["randf", "randfloat"],
-        precedence: 4,
+        precedence: 8,
         associativity: Associativity::Right,
-        arity: 2,
+        arity: Arity::Fixed(2),
         doit: |arr| rand::thread_rng().gen_range(arr[0]..=arr[1]),
     },
     Operator {
         kind: OperatorType::RandomInt,
         repr: &["randi", "randint"],
-        precedence: 4,
+        precedence: 8,
         associativity: Associativity::Right,
-        arity: 2,
+        arity: Arity::Fixed(2),
         doit: |arr| rand::thread_rng().gen_range((arr[0] as i64)..=(arr[1] as i64)) as f64,
     },
     Operator {
         kind: OperatorType::Negative,
         repr: &["-"],
-        precedence: 4,
+        precedence: 8,
         associativity: Associativity::Right,
-        arity: 1,
+        arity: Arity::Fixed(1),
         doit: |arr| -arr[0],
     },
     Operator {
         kind: OperatorType::Positive,
         repr: &["+"],
-        precedence: 4,
+        precedence: 8,
         associativity: Associativity::Right,
-        arity: 1,
+        arity: Arity::Fixed(1),
         doit: |arr| arr[0],
     },
 ];
@@ -263,7 +1041,7 @@ static OPERATORS: &[Operator] = &[
 #[cfg(test)]
 mod tests {
 
-    use super::{factorial, Operator, OperatorType};
+    use super::{choose, factorial, floor_mod, gcd, lcm, median, sign, Operator, OperatorType};
 
     #[test]
     fn test_factorial_normal() {
@@ -293,8 +1071,16 @@ mod tests {
 
     #[test]
     fn test_factorial_negative() {
-        let result = factorial(-1.0);
-        assert_same!(result, 1.0);
+        assert!(factorial(-1.0).is_nan());
+        // -0.5! = Γ(0.5) = √π
+        assert_same!(factorial(-0.5), std::f64::consts::PI.sqrt());
+    }
+
+    #[test]
+    fn test_factorial_fractional() {
+        // 2.5! = Γ(3.5) = 15√π / 8
+        let expected = 15.0 / 8.0 * std::f64::consts::PI.sqrt();
+        assert!((factorial(2.5) - expected).abs() < 1e-13);
     }
 
     #[test]
@@ -310,6 +1096,88 @@ mod tests {
         assert_eq!(cons.1, 3);
     }
 
+    #[test]
+    fn test_by_repr_longest() {
+        for &(repr, kind, len) in &[
+            ("sin(1)", OperatorType::Sin, 3),
+            ("sinh(1)", OperatorType::Sinh, 4),
+            ("log(1)", OperatorType::Log10, 3),
+            ("log2(1)", OperatorType::Log2, 4),
+            ("log10(1)", OperatorType::Log10, 5),
+            ("atan2(1, 2)", OperatorType::Atan2, 5),
+            ("ceiling(1)", OperatorType::Ceil, 7),
+            ("- 1", OperatorType::Sub, 1),
+            ("// 2", OperatorType::IntDiv, 2),
+            ("%% 2", OperatorType::FloorMod, 2),
+            ("factor(6)", OperatorType::Factor, 6),
+            ("fact(6)", OperatorType::Factorial, 4),
+        ] {
+            let (op, n) = Operator::by_repr(repr).unwrap();
+            assert_eq!(op.kind, kind, "Checking kind of [{}]", repr);
+            assert_eq!(n, len, "Checking length of [{}]", repr);
+        }
+    }
+
+    #[test]
+    fn test_by_repr_words() {
+        for &(repr, kind, len) in &[
+            ("re 2", OperatorType::Re, 2),
+            ("sin5", OperatorType::Sin, 3),
+            ("sinh(1)", OperatorType::Sinh, 4),
+            ("mod2", OperatorType::Mod, 3),
+        ] {
+            let (op, n) = Operator::by_repr(repr).unwrap();
+            assert_eq!(op.kind, kind, "Checking kind of [{}]", repr);
+            assert_eq!(n, len, "Checking length of [{}]", repr);
+        }
+        // Names followed by a letter are only the start of a longer word
+        for repr in &["rem 2", "sinx", "modulo", "tom"] {
+            assert!(Operator::by_repr(repr).is_none(), "Checking [{}]", repr);
+        }
+    }
+
+    #[test]
+    fn test_median() {
+        assert_same!(median(&[3.0]), 3.0);
+        assert_same!(median(&[3.0, 1.0, 2.0]), 2.0);
+        assert_same!(median(&[4.0, 1.0, 3.0, 2.0]), 2.5);
+    }
+
+    #[test]
+    fn test_gcd_lcm() {
+        assert_same!(gcd(12.0, 18.0), 6.0);
+        assert_same!(gcd(-12.0, 18.0), 6.0);
+        assert_same!(gcd(0.0, 5.0), 5.0);
+        assert!(gcd(1.5, 3.0).is_nan());
+        assert_same!(lcm(4.0, 6.0), 12.0);
+        assert_same!(lcm(0.0, 0.0), 0.0);
+        assert!(lcm(4.0, 0.5).is_nan());
+    }
+
+    #[test]
+    fn test_floor_mod() {
+        assert_same!(floor_mod(-7.0, 3.0), 2.0);
+        assert_same!(floor_mod(7.0, -3.0), -2.0);
+        assert_same!(floor_mod(-6.0, 3.0), 0.0);
+        assert_same!(floor_mod(5.5, 2.0), 1.5);
+        assert!(floor_mod(1.0, 0.0).is_nan());
+    }
+
+    #[test]
+    fn test_choose() {
+        assert_same!(choose(10.0, 3.0, false), 120.0);
+        assert_same!(choose(10.0, 3.0, true), 720.0);
+        assert_same!(choose(3.0, 10.0, false), 0.0);
+        assert!(choose(-1.0, 1.0, false).is_nan());
+    }
+
+    #[test]
+    fn test_sign() {
+        assert_same!(sign(-5.5), -1.0);
+        assert_same!(sign(0.0), 0.0);
+        assert_same!(sign(3.0), 1.0);
+    }
+
     #[test]
     fn test_is() {
         assert!(Operator::is("sin"));
@@ -0,0 +1,370 @@
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use super::errors::Error;
+
+/// Called between the expensive steps of a computation. Its error stops the computation, e.g. when time runs out
+pub type Interrupt<'a> = &'a dyn Fn() -> Result<(), Error>;
+
+/// An `Interrupt` that never stops a computation
+#[allow(clippy::unnecessary_wraps)]
+const fn uninterrupted() -> Result<(), Error> {
+    Ok(())
+}
+
+/// Witnesses for the Miller-Rabin test, the first twelve primes.
+/// These alone make the test deterministic below `3.1 × 10^23`
+const WITNESSES: [u8; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+/// Odd numbers below this are tried as divisors before the Miller-Rabin test, which rules out most composites cheaply
+const TRIAL_LIMIT: u32 = 1000;
+
+/// The largest integers, in bits, that `is_prime` and `next_prime` accept, about 308 digits.
+/// The cost of the Miller-Rabin test grows with the cube of the size, and `next_prime` may run it hundreds of times
+pub const MAX_BITS: u64 = 1024;
+
+/// Determines if `n` is prime, by the Miller-Rabin test followed by a strong Lucas test.
+/// Together they're the Baillie-PSW test, which no composite is known to pass
+/// * `interrupt` - Checked before each round of the tests
+///
+/// ## Errors
+/// Returns the error of `interrupt`, if it stops the test
+pub fn is_prime(n: &BigInt, interrupt: Interrupt) -> Result<bool, Error> {
+    if *n < BigInt::from(2) {
+        return Ok(false);
+    }
+    for &p in &WITNESSES {
+        if *n == BigInt::from(p) {
+            return Ok(true);
+        }
+        if n.is_multiple_of(&BigInt::from(p)) {
+            return Ok(false);
+        }
+    }
+    // Any composite reached here has a smaller odd factor that was already tried, so `n` is prime if it's equal to one
+    for d in (u32::from(WITNESSES[11]) + 2..TRIAL_LIMIT).step_by(2) {
+        if *n == BigInt::from(d) {
+            return Ok(true);
+        }
+        if (n % d).is_zero() {
+            return Ok(false);
+        }
+    }
+
+    // n - 1 = d · 2^s, with d odd
+    let n_minus_one: BigInt = n - 1;
+    let s = n_minus_one.trailing_zeros().unwrap_or(0);
+    let d = &n_minus_one >> s;
+    for &a in &WITNESSES {
+        interrupt()?;
+        let mut x = BigInt::from(a).modpow(&d, n);
+        if x.is_one() || x == n_minus_one {
+            continue;
+        }
+        let mut witnessed = true;
+        for _ in 1..s {
+            x = &x * &x % n;
+            if x == n_minus_one {
+                witnessed = false;
+                break;
+            }
+        }
+        // `a` is a witness to the compositeness of `n`
+        if witnessed {
+            return Ok(false);
+        }
+    }
+    interrupt()?;
+    Ok(is_strong_lucas_probable_prime(n))
+}
+
+/// Compute the Jacobi symbol `(a / n)`, for odd positive `n`
+fn jacobi(a: &BigInt, n: &BigInt) -> i8 {
+    let (mut a, mut n) = (a.mod_floor(n), n.clone());
+    let mut result = 1;
+    while !a.is_zero() {
+        while a.is_even() {
+            a >>= 1_u32;
+            // (2 / n) is -1 when n is 3 or 5 modulo 8
+            if [3, 5].contains(&(&n % 8_u32).to_u8().unwrap_or(0)) {
+                result = -result;
+            }
+        }
+        std::mem::swap(&mut a, &mut n);
+        // Quadratic reciprocity flips the sign when both are 3 modulo 4
+        if (&a % 4_u32).to_u8() == Some(3) && (&n % 4_u32).to_u8() == Some(3) {
+            result = -result;
+        }
+        a = a.mod_floor(&n);
+    }
+    if n.is_one() {
+        result
+    } else {
+        0
+    }
+}
+
+/// The strong Lucas probable prime test, with Selfridge's parameters, for odd `n` with no small factors
+fn is_strong_lucas_probable_prime(n: &BigInt) -> bool {
+    // Squares have no suitable discriminant
+    let root = n.sqrt();
+    if &root * &root == *n {
+        return false;
+    }
+
+    // The first of 5, -7, 9, -11, ... with a Jacobi symbol of -1
+    let mut discriminant = BigInt::from(5);
+    loop {
+        match jacobi(&discriminant, n) {
+            -1 => break,
+            0 if discriminant.magnitude() != n.magnitude() => return false,
+            _ => {
+                discriminant = if discriminant.is_positive() {
+                    -discriminant - 2
+                } else {
+                    -discriminant + 2
+                }
+            }
+        }
+    }
+    let q: BigInt = (1 - &discriminant) / 4;
+
+    // Halve `x` modulo the odd `n`
+    let half = |x: BigInt| {
+        let x = if x.is_odd() { x + n } else { x };
+        (x >> 1_u32).mod_floor(n)
+    };
+
+    // n + 1 = odd · 2^doublings
+    let n_plus_one: BigInt = n + 1;
+    let doublings = n_plus_one.trailing_zeros().unwrap_or(0);
+    let odd = &n_plus_one >> doublings;
+
+    // The Lucas sequences U and V with P = 1, computed along with Q^i for an index i built up from the bits of `odd`
+    let (mut u, mut v, mut q_i) = (BigInt::one(), BigInt::one(), q.mod_floor(n));
+    for bit in (0..odd.bits() - 1).rev() {
+        // Double the index
+        u = (&u * &v).mod_floor(n);
+        v = (&v * &v - (&q_i << 1_u32)).mod_floor(n);
+        q_i = (&q_i * &q_i).mod_floor(n);
+        // Increment the index
+        if odd.bit(bit) {
+            let next_u = half(&u + &v);
+            v = half(&discriminant * &u + &v);
+            u = next_u;
+            q_i = (&q_i * &q).mod_floor(n);
+        }
+    }
+
+    if u.is_zero() || v.is_zero() {
+        return true;
+    }
+    for _ in 1..doublings {
+        v = (&v * &v - (&q_i << 1_u32)).mod_floor(n);
+        if v.is_zero() {
+            return true;
+        }
+        q_i = (&q_i * &q_i).mod_floor(n);
+    }
+    false
+}
+
+/// Find the smallest prime greater than `n`
+/// * `interrupt` - Checked before testing each candidate, see [`is_prime`]
+///
+/// ## Errors
+/// Returns the error of `interrupt`, if it stops the search
+pub fn next_prime(n: &BigInt, interrupt: Interrupt) -> Result<BigInt, Error> {
+    let two = BigInt::from(2);
+    if *n < two {
+        return Ok(two);
+    }
+    let mut candidate: BigInt = n + 1;
+    if candidate.is_even() {
+        candidate += 1;
+    }
+    while !is_prime(&candidate, interrupt)? {
+        candidate += 2;
+    }
+    Ok(candidate)
+}
+
+/// Compute `a * b + c` modulo `m`, without overflowing
+#[allow(clippy::cast_possible_truncation)]
+fn mul_add_mod(a: u64, b: u64, c: u64, m: u64) -> u64 {
+    ((u128::from(a) * u128::from(b) + u128::from(c)) % u128::from(m)) as u64
+}
+
+/// Find a non-trivial divisor of the odd composite `n`, by Pollard's rho algorithm
+fn pollard_rho(n: u64) -> u64 {
+    let mut increment = 1;
+    loop {
+        let next = |x: u64| mul_add_mod(x, x, increment, n);
+        // Floyd's cycle detection, the hare moves twice as fast as the tortoise
+        let (mut tortoise, mut hare, mut divisor) = (2, 2, 1);
+        while divisor == 1 {
+            tortoise = next(tortoise);
+            hare = next(next(hare));
+            divisor = tortoise.abs_diff(hare).gcd(&n);
+        }
+        // The sequence cycled without finding a divisor, so try another one
+        if divisor != n {
+            return divisor;
+        }
+        increment += 1;
+    }
+}
+
+/// Add the prime factors of `n` to `factors`, unordered and with repeats
+fn split(n: u64, factors: &mut Vec<u64>) {
+    if n == 1 {
+        return;
+    }
+    if is_prime(&BigInt::from(n), &uninterrupted) == Ok(true) {
+        factors.push(n);
+        return;
+    }
+    let divisor = pollard_rho(n);
+    split(divisor, factors);
+    split(n / divisor, factors);
+}
+
+/// Compute the prime factorization of `n` as pairs of primes and their powers, from smallest to largest.
+/// Zero and one have no prime factors
+pub fn factorize(mut n: u64) -> Vec<(u64, u32)> {
+    if n < 2 {
+        return Vec::new();
+    }
+    let mut factors = Vec::new();
+
+    // Small factors are found quickly by trial division, which leaves an odd number for Pollard's rho
+    for p in WITNESSES.iter().map(|&p| u64::from(p)) {
+        while n.is_multiple_of(p) {
+            factors.push(p);
+            n /= p;
+        }
+    }
+    split(n, &mut factors);
+
+    factors.sort_unstable();
+    let mut powers: Vec<(u64, u32)> = Vec::new();
+    for p in factors {
+        match powers.last_mut() {
+            Some((last, power)) if *last == p => *power += 1,
+            _ => powers.push((p, 1)),
+        }
+    }
+    powers
+}
+
+/// Compute the prime factorization of `n`, or `None` if it's too large to factor in a reasonable time
+pub fn factorize_big(n: &BigInt) -> Option<Vec<(u64, u32)>> {
+    n.magnitude().to_u64().map(factorize)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use num_bigint::BigInt;
+
+    use super::{factorize, is_strong_lucas_probable_prime, next_prime, uninterrupted};
+    use crate::model::{errors::Error, span::Span};
+
+    fn is_prime(n: &BigInt) -> bool {
+        super::is_prime(n, &uninterrupted).unwrap()
+    }
+
+    #[test]
+    fn test_is_prime() {
+        let primes: Vec<i64> = (-5..50).filter(|&n| is_prime(&BigInt::from(n))).collect();
+        assert_eq!(
+            primes,
+            [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47]
+        );
+        // Mersenne primes, and a Carmichael number
+        assert!(is_prime(&BigInt::from(2_147_483_647_u64)));
+        assert!(is_prime(&((BigInt::from(1) << 127_u32) - 1)));
+        assert!(!is_prime(&BigInt::from(561)));
+        // Primes and composites on either side of the trial divisors
+        assert!(is_prime(&BigInt::from(997)));
+        assert!(!is_prime(&BigInt::from(41 * 43)));
+        assert!(!is_prime(&BigInt::from(1009 * 1013)));
+        // Strong pseudoprimes to every Miller-Rabin witness, which the Lucas test catches
+        for n in &["318665857834031151167461", "3317044064679887385961981"] {
+            assert!(!is_prime(&n.parse().unwrap()), "Checking [{}]", n);
+        }
+        // Lucas pseudoprimes, which the Miller-Rabin test catches
+        for &n in &[5459, 5777, 10877, 16109, 18971] {
+            assert!(!is_prime(&BigInt::from(n)), "Checking [{}]", n);
+        }
+        assert!(is_prime(&((BigInt::from(1) << 521_u32) - 1)));
+        assert!(!is_prime(&((BigInt::from(1) << 523_u32) - 1)));
+    }
+
+    #[test]
+    fn test_strong_lucas() {
+        let primes: Vec<u32> = (1000..1100)
+            .filter(|n| n % 2 == 1)
+            .filter(|&n| is_strong_lucas_probable_prime(&BigInt::from(n)))
+            .collect();
+        assert_eq!(
+            primes,
+            [
+                1009, 1013, 1019, 1021, 1031, 1033, 1039, 1049, 1051, 1061, 1063, 1069, 1087, 1091,
+                1093, 1097
+            ]
+        );
+        // Strong Lucas pseudoprimes pass, they're left to the Miller-Rabin test
+        for &n in &[5459, 5777, 10877, 16109, 18971] {
+            assert!(is_strong_lucas_probable_prime(&BigInt::from(n)));
+        }
+        assert!(!is_strong_lucas_probable_prime(&BigInt::from(1009 * 1009)));
+    }
+
+    #[test]
+    fn test_next_prime() {
+        for &(n, expected) in &[(-10, 2), (2, 3), (13, 17), (1_000_000, 1_000_003)] {
+            let prime = next_prime(&BigInt::from(n), &uninterrupted).unwrap();
+            assert_eq!(prime, BigInt::from(expected));
+        }
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let timeout = || Err(Error::Timeout(Span::default()));
+        let big = (BigInt::from(1) << 127_u32) - 1;
+        assert_eq!(
+            super::is_prime(&big, &timeout),
+            Err(Error::Timeout(Span::default()))
+        );
+        assert!(next_prime(&big, &timeout).is_err());
+        // Small numbers never get as far as the Miller-Rabin test
+        assert_eq!(super::is_prime(&BigInt::from(97), &timeout), Ok(true));
+    }
+
+    #[test]
+    fn test_factorize() {
+        assert_eq!(factorize(0), []);
+        assert_eq!(factorize(1), []);
+        assert_eq!(factorize(360), [(2, 3), (3, 2), (5, 1)]);
+        assert_eq!(factorize(97), [(97, 1)]);
+        // The product of two large primes
+        assert_eq!(
+            factorize(4_294_967_291 * 4_294_967_279),
+            [(4_294_967_279, 1), (4_294_967_291, 1)]
+        );
+        assert_eq!(
+            factorize(u64::MAX),
+            [
+                (3, 1),
+                (5, 1),
+                (17, 1),
+                (257, 1),
+                (641, 1),
+                (65_537, 1),
+                (6_700_417, 1)
+            ]
+        );
+    }
+}
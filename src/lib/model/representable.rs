@@ -3,21 +3,43 @@ pub trait Representable {
 }
 
 pub trait Searchable {
-    fn search<'a>(&'a self, search: &str) -> Option<(&'a Self, usize)>;
+    /// * `words` - Whether alphabetic representations must be whole words, e.g. so that `re` doesn't match `rem`
+    fn search<'a>(&'a self, search: &str, words: bool) -> Option<(&'a Self, usize)>;
 }
 
 impl<Repr: Representable> Searchable for Repr {
-    fn search<'a>(&'a self, search: &str) -> Option<(&'a Self, usize)> {
+    fn search<'a>(&'a self, search: &str, words: bool) -> Option<(&'a Self, usize)> {
+        let search = search.to_lowercase();
+        // A letter after an alphabetic representation means it's only the start of a longer word
+        let whole = |len: usize, repr: &str| {
+            !words
+                || !repr.chars().last().is_some_and(char::is_alphabetic)
+                || !search.chars().nth(len).is_some_and(char::is_alphabetic)
+        };
         self.repr()
             .iter()
-            .find(|repr| search.to_lowercase().starts_with(&repr.to_lowercase()))
-            .map(|repr| (self, repr.chars().count()))
+            .filter(|repr| search.starts_with(&repr.to_lowercase()))
+            .map(|repr| (repr.chars().count(), repr))
+            .filter(|(len, repr)| whole(*len, repr))
+            .map(|(len, _)| len)
+            .max()
+            .map(|len| (self, len))
     }
 }
 
-pub(super) fn get_by_repr<'a, T: Searchable>(
+/// Finds the item in `list` with the longest representation that `search` starts with.
+/// Ties are broken by order in `list`. e.g. `sinh` matches `sinh` rather than `sin`
+/// * `words` - Whether alphabetic representations must be whole words, see [`Searchable::search`]
+pub(super) fn get_longest_by_repr<'a, T: Searchable>(
     search: &str,
     list: &'a [T],
+    words: bool,
 ) -> Option<(&'a T, usize)> {
-    list.iter().find_map(|t| t.search(search))
+    list.iter().filter_map(|t| t.search(search, words)).fold(
+        None,
+        |longest, (t, len)| match longest {
+            Some((_, max)) if max >= len => longest,
+            _ => Some((t, len)),
+        },
+    )
 }
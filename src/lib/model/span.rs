@@ -0,0 +1,74 @@
+use std::ops::Deref;
+
+/// A range of characters in the source of an expression, from `start` up to but not including `end`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub const fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// An empty span at `idx`, e.g. for tokens that were inserted rather than parsed
+    pub const fn at(idx: usize) -> Self {
+        Self::new(idx, idx)
+    }
+
+    pub const fn len(self) -> usize {
+        self.end - self.start
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.start == self.end
+    }
+
+    /// The smallest span that covers both `self` and `other`
+    #[must_use]
+    pub fn to(self, other: Self) -> Self {
+        Self::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    /// Shift this span `offset` characters to the right
+    #[must_use]
+    pub const fn offset(self, offset: usize) -> Self {
+        Self::new(self.start + offset, self.end + offset)
+    }
+}
+
+/// A value along with the span of source it came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Spanned<T> {
+    pub inner: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub const fn new(inner: T, span: Span) -> Self {
+        Self { inner, span }
+    }
+}
+
+/// A value with an empty span at the start of the source
+impl<T> From<T> for Spanned<T> {
+    fn from(inner: T) -> Self {
+        Self::new(inner, Span::default())
+    }
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+/// Compares only the value, ignoring the span
+impl<T: PartialEq> PartialEq<T> for Spanned<T> {
+    fn eq(&self, other: &T) -> bool {
+        self.inner == *other
+    }
+}
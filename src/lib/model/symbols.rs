@@ -0,0 +1,229 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeSet, HashMap},
+    fmt,
+    hash::BuildHasherDefault,
+    iter::FromIterator,
+    ops::Deref,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use super::{functions::Function, variables::Variable};
+
+/// Something that can be stored in a `SymbolTable`
+pub trait Named {
+    fn name(&self) -> &str;
+}
+
+impl Named for Variable {
+    fn name(&self) -> &str {
+        &self.repr
+    }
+}
+
+impl Named for Function {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// The most recently assigned generation, see [`SymbolTable::generation`]
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// A generation that hasn't been assigned to anything before
+pub fn next_generation() -> u64 {
+    GENERATION.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// A collection of named items, e.g. variables or functions, indexed by name.
+///
+/// Items are kept in the order they were first inserted, and names are resolved by longest match
+/// so that, with `x` and `xx` defined, `xx` always refers to `xx` regardless of that order.
+#[derive(Clone)]
+pub struct SymbolTable<T> {
+    items: Vec<T>,
+    /// Maps each name to the index of its item
+    index: HashMap<String, usize, BuildHasherDefault<DefaultHasher>>,
+    /// The distinct lengths of the names in the table, in bytes
+    lengths: BTreeSet<usize>,
+    /// Changes whenever an item is inserted or removed
+    generation: u64,
+}
+
+impl<T: Named> SymbolTable<T> {
+    pub const fn new() -> Self {
+        Self {
+            items: Vec::new(),
+            index: HashMap::with_hasher(BuildHasherDefault::new()),
+            lengths: BTreeSet::new(),
+            generation: 0,
+        }
+    }
+
+    /// Identifies the contents of the table: two tables with the same generation have the same names.
+    /// Every empty table created with [`SymbolTable::new`] has a generation of zero
+    pub const fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Set the generation of a table whose names are known to be the same as others with `generation`,
+    /// e.g. the arguments of a function
+    pub(crate) const fn with_generation(mut self, generation: u64) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    /// Get the item named exactly `name`
+    pub fn get(&self, name: &str) -> Option<&T> {
+        self.index.get(name).map(|idx| &self.items[*idx])
+    }
+
+    /// Insert an item, replacing and returning the item with the same name if there is one.
+    /// A replaced item keeps its position.
+    pub fn insert(&mut self, item: T) -> Option<T> {
+        self.generation = next_generation();
+        if let Some(idx) = self.index.get(item.name()) {
+            return Some(std::mem::replace(&mut self.items[*idx], item));
+        }
+        self.lengths.insert(item.name().len());
+        self.index.insert(item.name().to_string(), self.items.len());
+        self.items.push(item);
+        None
+    }
+
+    /// Remove and return the item named exactly `name`, if there is one.
+    /// The remaining items keep their order.
+    pub fn remove(&mut self, name: &str) -> Option<T> {
+        let idx = self.index.remove(name)?;
+        self.generation = next_generation();
+        let item = self.items.remove(idx);
+        // Every later item has moved down by one
+        for later in self.index.values_mut().filter(|later| **later > idx) {
+            *later -= 1;
+        }
+        self.lengths = self.items.iter().map(|item| item.name().len()).collect();
+        Some(item)
+    }
+
+    /// Find the item with the longest name that `text` starts with
+    ///
+    /// ## Returns
+    /// The item and the length of its name in characters
+    pub fn longest_match(&self, text: &str) -> Option<(&T, usize)> {
+        self.lengths
+            .iter()
+            .rev()
+            .filter(|len| text.is_char_boundary(**len))
+            .find_map(|len| self.get(&text[..*len]))
+            .map(|item| (item, item.name().chars().count()))
+    }
+}
+
+impl<T: Named> Default for SymbolTable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Deref for SymbolTable<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.items
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SymbolTable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(&self.items).finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for SymbolTable<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.items == other.items
+    }
+}
+
+/// Later items replace earlier ones with the same name
+impl<T: Named> FromIterator<T> for SymbolTable<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut table = Self::new();
+        iter.into_iter().for_each(|item| {
+            table.insert(item);
+        });
+        table
+    }
+}
+
+impl<T: Named, const N: usize> From<[T; N]> for SymbolTable<T> {
+    fn from(items: [T; N]) -> Self {
+        IntoIterator::into_iter(items).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    #![allow(clippy::non_ascii_literal)]
+
+    use super::SymbolTable;
+    use crate::model::variables::Variable;
+
+    fn var(repr: &str, value: f64) -> Variable {
+        Variable {
+            repr: repr.to_string(),
+            value: value.into(),
+        }
+    }
+
+    #[test]
+    fn test_longest_match() {
+        // The result doesn't depend on the order of insertion
+        let tables = [
+            SymbolTable::from([var("x", 1.0), var("xx", 2.0), var("😂", 3.0)]),
+            SymbolTable::from([var("😂", 3.0), var("xx", 2.0), var("x", 1.0)]),
+        ];
+        for table in &tables {
+            let (found, len) = table.longest_match("xx + 1").unwrap();
+            assert_eq!((found.repr.as_str(), len), ("xx", 2));
+            let (found, len) = table.longest_match("x").unwrap();
+            assert_eq!((found.repr.as_str(), len), ("x", 1));
+            let (found, len) = table.longest_match("xy").unwrap();
+            assert_eq!((found.repr.as_str(), len), ("x", 1));
+            let (found, len) = table.longest_match("😂😂").unwrap();
+            assert_eq!((found.repr.as_str(), len), ("😂", 1));
+            assert!(table.longest_match("y").is_none());
+            assert!(table.longest_match("").is_none());
+        }
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut table = SymbolTable::from([var("a", 1.0), var("b", 2.0)]);
+        let generation = table.generation();
+        assert_eq!(table.insert(var("a", 3.0)), Some(var("a", 1.0)));
+        assert_ne!(table.generation(), generation);
+        assert_eq!(table.insert(var("c", 4.0)), None);
+
+        let names: Vec<&str> = table.iter().map(|v| v.repr.as_str()).collect();
+        assert_eq!(names, ["a", "b", "c"]);
+        assert_eq!(table.get("a"), Some(&var("a", 3.0)));
+        assert_eq!(table.get("d"), None);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut table = SymbolTable::from([var("a", 1.0), var("bb", 2.0), var("c", 3.0)]);
+        let generation = table.generation();
+        assert_eq!(table.remove("bb"), Some(var("bb", 2.0)));
+        assert_ne!(table.generation(), generation);
+        let generation = table.generation();
+        assert_eq!(table.remove("bb"), None);
+        assert_eq!(table.generation(), generation);
+
+        let names: Vec<&str> = table.iter().map(|v| v.repr.as_str()).collect();
+        assert_eq!(names, ["a", "c"]);
+        assert_eq!(table.get("c"), Some(&var("c", 3.0)));
+        assert!(table.longest_match("bb").is_none());
+    }
+}
@@ -1,28 +1,46 @@
 #![allow(clippy::non_ascii_literal, clippy::bind_instead_of_map)]
 
+use num_bigint::BigInt;
+
 use super::{
     constants::{Constant, ConstantType},
     functions::Functions,
+    number::Literal,
     operators::{Operator, OperatorType},
+    units::PrefixedUnit,
     variables::Variable,
 };
 
 const NUMBER_CHARACTERS: [char; 11] = ['0', '1', '2', '3', '4', '5', '6', '7', '8', '9', '.'];
+const DIGIT_SEPARATOR: char = '_';
+const EXPONENT_CHARACTERS: [char; 2] = ['e', 'E'];
+const SIGN_CHARACTERS: [char; 2] = ['+', '-'];
+
+/// Prefixes for non-decimal integer literals and their radix
+const RADIX_PREFIXES: [(&str, u32); 6] = [
+    ("0x", 16),
+    ("0X", 16),
+    ("0b", 2),
+    ("0B", 2),
+    ("0o", 8),
+    ("0O", 8),
+];
 const PAREN_CHARACTERS: [char; 2] = ['(', ')'];
 const COMMA_CHARACTERS: [char; 1] = [','];
 
-#[derive(PartialEq, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum ParenType {
     Left,
     Right,
 }
 
-#[derive(Clone, Debug, Copy, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Token<'a> {
-    Number { value: f64 },
+    Number { value: Literal },
     Operator { inner: Functions<'a> },
     Paren { kind: ParenType },
     Constant { inner: &'a Constant },
+    Unit { inner: PrefixedUnit },
     Variable { inner: &'a Variable },
     Comma,
 }
@@ -38,12 +56,22 @@ impl Token<'_> {
             _ => None,
         }
     }
+    /// Parse the number at the start of `string`.
+    /// Supports decimals (`1.5`), exponents (`6.022e23`, `1E-9`), digit separators (`1_000`),
+    /// and hexadecimal, binary, and octal integers (`0x1F`, `0b1010`, `0o17`)
+    ///
+    /// Returns the token and the number of characters it spans
     pub fn number(string: &str) -> Option<(Self, usize)> {
         let repr = Self::next_number(string);
-        match repr.parse::<f64>() {
-            Ok(value) => Some((Self::Number { value }, repr.len())),
-            Err(..) => None,
-        }
+        let digits: String = repr.chars().filter(|c| *c != DIGIT_SEPARATOR).collect();
+        let value = match Self::radix(&repr) {
+            Some((prefix, radix)) => Literal::integer(BigInt::parse_bytes(
+                &digits.as_bytes()[prefix.len()..],
+                radix,
+            )?),
+            None => Literal::parse(&digits)?,
+        };
+        Some((Self::Number { value }, repr.len()))
     }
     pub fn operator(kind: OperatorType) -> Self {
         Self::Operator {
@@ -55,14 +83,63 @@ impl Token<'_> {
             inner: Constant::by_type(kind),
         }
     }
+    /// Determines if `string` starts with a radix prefix that is followed by a digit in that radix
+    fn radix(string: &str) -> Option<(&'static str, u32)> {
+        RADIX_PREFIXES.iter().copied().find(|(prefix, radix)| {
+            string.starts_with(prefix)
+                && string[prefix.len()..]
+                    .chars()
+                    .next()
+                    .is_some_and(|c| c.is_digit(*radix))
+        })
+    }
+
+    /// Extract the representation of the number at the start of `string`
     fn next_number(string: &str) -> String {
-        string
-            .chars()
-            .take_while(|c| NUMBER_CHARACTERS.contains(c))
-            .collect::<String>()
+        let chars: Vec<char> = string.chars().collect();
+
+        // Digit separators are only allowed between digits
+        let separated = |idx: usize, radix: u32| {
+            chars[idx] == DIGIT_SEPARATOR
+                && idx > 0
+                && chars[idx - 1].is_digit(radix)
+                && chars.get(idx + 1).is_some_and(|c| c.is_digit(radix))
+        };
+
+        if let Some((prefix, radix)) = Self::radix(string) {
+            let len = (prefix.len()..chars.len())
+                .find(|idx| !(chars[*idx].is_digit(radix) || separated(*idx, radix)))
+                .unwrap_or(chars.len());
+            return chars[..len].iter().collect();
+        }
+
+        let mut len = (0..chars.len())
+            .find(|idx| !(NUMBER_CHARACTERS.contains(&chars[*idx]) || separated(*idx, 10)))
+            .unwrap_or(chars.len());
+
+        // An exponent is only consumed when it's followed by digits
+        // Otherwise, e.g. in `2e`, the `e` is Euler's number
+        if chars
+            .get(len)
+            .is_some_and(|c| EXPONENT_CHARACTERS.contains(c))
+        {
+            let sign = usize::from(
+                chars
+                    .get(len + 1)
+                    .is_some_and(|c| SIGN_CHARACTERS.contains(c)),
+            );
+            let start = len + 1 + sign;
+            if chars.get(start).is_some_and(char::is_ascii_digit) {
+                len = (start..chars.len())
+                    .find(|idx| !(chars[*idx].is_ascii_digit() || separated(*idx, 10)))
+                    .unwrap_or(chars.len());
+            }
+        }
+
+        chars[..len].iter().collect()
     }
     fn is_next_t(string: &str, list: &[char]) -> bool {
-        string.chars().next().map_or(false, |c| list.contains(&c))
+        string.chars().next().is_some_and(|c| list.contains(&c))
     }
     pub fn is_next_number(string: &str) -> bool {
         Self::is_next_t(string, &NUMBER_CHARACTERS)
@@ -79,7 +156,7 @@ impl Token<'_> {
 mod tests {
     #![allow(clippy::shadow_unrelated)]
 
-    use super::{ParenType, Token};
+    use super::{Literal, ParenType, Token};
 
     #[test]
     fn test_paren() {
@@ -101,7 +178,7 @@ mod tests {
         assert_eq!(r_paren.1, ParenType::Right);
 
         let none = Token::paren('a');
-        assert!(matches!(none, None));
+        assert!(none.is_none());
     }
 
     #[test]
@@ -109,6 +186,22 @@ mod tests {
         assert_eq!(Token::next_number("1234567890"), "1234567890");
         assert_eq!(Token::next_number("1.234"), "1.234");
         assert_eq!(Token::next_number("555"), "555");
+        assert_eq!(Token::next_number("6.022e23 + 1"), "6.022e23");
+        assert_eq!(Token::next_number("1E-9"), "1E-9");
+        assert_eq!(Token::next_number("2e+3"), "2e+3");
+        assert_eq!(Token::next_number("1_000_000"), "1_000_000");
+        assert_eq!(Token::next_number("0x1F"), "0x1F");
+        assert_eq!(Token::next_number("0b1010_0101"), "0b1010_0101");
+        assert_eq!(Token::next_number("0o17"), "0o17");
+
+        // These aren't part of the number
+        assert_eq!(Token::next_number("2e"), "2");
+        assert_eq!(Token::next_number("2e-"), "2");
+        assert_eq!(Token::next_number("2eq"), "2");
+        assert_eq!(Token::next_number("1__0"), "1");
+        assert_eq!(Token::next_number("1_"), "1");
+        assert_eq!(Token::next_number("0x"), "0");
+        assert_eq!(Token::next_number("0b12"), "0b1");
     }
 
     #[test]
@@ -116,16 +209,50 @@ mod tests {
         let result = Token::number("123").unwrap();
         assert_eq!(result.1, 3);
         match result.0 {
-            Token::Number { value } => assert_same!(value, 123.0),
+            Token::Number { value } => assert_eq!(value, Literal::from(123.0)),
             _ => panic!("Expected a number"),
-        };
+        }
 
         let result = Token::number("999.544").unwrap();
         assert_eq!(result.1, 7);
         match result.0 {
-            Token::Number { value } => assert_same!(value, 999.544),
+            Token::Number { value } => assert_eq!(value, Literal::from(999.544)),
             _ => panic!("Expected a number"),
-        };
+        }
+
+        for (string, expected, len) in &[
+            ("6.022e23", 6.022e23, 8),
+            ("1E-9", 1e-9, 4),
+            ("1_000_000", 1_000_000.0, 9),
+            ("0x1F", 31.0, 4),
+            ("0b1010", 10.0, 6),
+            ("0o17", 15.0, 4),
+            ("0xff_ff", 65535.0, 7),
+            ("9_007_199_254_740_992", 9_007_199_254_740_992.0, 21),
+        ] {
+            let result = Token::number(string).unwrap();
+            assert_eq!(result.1, *len, "Checking length of [{}]", string);
+            match result.0 {
+                Token::Number { value } => assert_eq!(value.to_string(), expected.to_string()),
+                _ => panic!("Expected a number"),
+            }
+        }
+
+        // Integers of any size are exact
+        for (string, expected) in &[
+            ("9007199254740993", "9007199254740993"),
+            (
+                "0xFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF",
+                "87112285931760246646623899502532662132735",
+            ),
+        ] {
+            match Token::number(string).unwrap().0 {
+                Token::Number { value } => assert_eq!(value.to_string(), *expected),
+                _ => panic!("Expected a number"),
+            }
+        }
+
+        assert!(Token::number("1.2.3").is_none());
     }
 
     #[test]
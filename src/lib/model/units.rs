@@ -0,0 +1,661 @@
+#![allow(clippy::non_ascii_literal)]
+
+use std::{convert::TryFrom, fmt};
+
+use itertools::Itertools;
+
+use super::{
+    errors::Error,
+    number::Number,
+    operators::{Operator, OperatorType},
+    span::Span,
+};
+
+/// The exponents of the SI base dimensions, in order:
+/// length, mass, time, electric current, temperature, amount of substance, and luminous intensity
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Dimension(pub [i8; 7]);
+
+impl Dimension {
+    const LENGTH: Self = Self([1, 0, 0, 0, 0, 0, 0]);
+    const MASS: Self = Self([0, 1, 0, 0, 0, 0, 0]);
+    const TIME: Self = Self([0, 0, 1, 0, 0, 0, 0]);
+    const CURRENT: Self = Self([0, 0, 0, 1, 0, 0, 0]);
+    const TEMPERATURE: Self = Self([0, 0, 0, 0, 1, 0, 0]);
+    const AMOUNT: Self = Self([0, 0, 0, 0, 0, 1, 0]);
+    const LUMINOSITY: Self = Self([0, 0, 0, 0, 0, 0, 1]);
+
+    /// Determines if this is the dimension of a plain number
+    pub fn is_dimensionless(self) -> bool {
+        self == Self::default()
+    }
+
+    /// The dimension of the product of quantities with dimensions `self` and `other`,
+    /// or `None` if an exponent overflows
+    pub fn times(self, other: Self) -> Option<Self> {
+        let mut out = self;
+        for (a, b) in out.0.iter_mut().zip(other.0.iter()) {
+            *a = a.checked_add(*b)?;
+        }
+        Some(out)
+    }
+
+    /// The dimension of a quantity with dimension `self` raised to the power of `n`,
+    /// or `None` if an exponent overflows
+    pub fn pow(self, n: i32) -> Option<Self> {
+        let n = i8::try_from(n).ok()?;
+        let mut out = self;
+        for a in &mut out.0 {
+            *a = a.checked_mul(n)?;
+        }
+        Some(out)
+    }
+}
+
+/// A unit of measurement
+#[derive(Debug, PartialEq)]
+pub struct Unit {
+    /// The first representation is the unit's symbol
+    pub repr: &'static [&'static str],
+    /// The size of the unit in SI base units
+    pub scale: f64,
+    pub dimension: Dimension,
+    /// Whether the unit's symbol accepts SI prefixes, e.g. `km`
+    pub prefixable: bool,
+}
+
+/// An SI prefix, e.g. kilo
+#[derive(Debug, PartialEq)]
+pub struct Prefix {
+    pub repr: &'static [&'static str],
+    pub scale: f64,
+}
+
+static PREFIXES: &[Prefix] = &[
+    Prefix {
+        repr: &["T"],
+        scale: 1e12,
+    },
+    Prefix {
+        repr: &["G"],
+        scale: 1e9,
+    },
+    Prefix {
+        repr: &["M"],
+        scale: 1e6,
+    },
+    Prefix {
+        repr: &["k"],
+        scale: 1e3,
+    },
+    Prefix {
+        repr: &["d"],
+        scale: 1e-1,
+    },
+    Prefix {
+        repr: &["c"],
+        scale: 1e-2,
+    },
+    Prefix {
+        repr: &["m"],
+        scale: 1e-3,
+    },
+    Prefix {
+        repr: &["μ", "u"],
+        scale: 1e-6,
+    },
+    Prefix {
+        repr: &["n"],
+        scale: 1e-9,
+    },
+    Prefix {
+        repr: &["p"],
+        scale: 1e-12,
+    },
+];
+
+static UNITS: &[Unit] = &[
+    Unit {
+        repr: &["m", "meter", "meters", "metre", "metres"],
+        scale: 1.0,
+        dimension: Dimension::LENGTH,
+        prefixable: true,
+    },
+    Unit {
+        repr: &["g", "gram", "grams"],
+        scale: 1e-3,
+        dimension: Dimension::MASS,
+        prefixable: true,
+    },
+    Unit {
+        repr: &["s", "sec", "second", "seconds"],
+        scale: 1.0,
+        dimension: Dimension::TIME,
+        prefixable: true,
+    },
+    Unit {
+        repr: &["A", "amp", "amps", "ampere", "amperes"],
+        scale: 1.0,
+        dimension: Dimension::CURRENT,
+        prefixable: true,
+    },
+    Unit {
+        repr: &["K", "kelvin"],
+        scale: 1.0,
+        dimension: Dimension::TEMPERATURE,
+        prefixable: true,
+    },
+    Unit {
+        repr: &["mol", "mole", "moles"],
+        scale: 1.0,
+        dimension: Dimension::AMOUNT,
+        prefixable: true,
+    },
+    Unit {
+        repr: &["cd", "candela"],
+        scale: 1.0,
+        dimension: Dimension::LUMINOSITY,
+        prefixable: true,
+    },
+    Unit {
+        repr: &["in", "inch", "inches"],
+        scale: 0.0254,
+        dimension: Dimension::LENGTH,
+        prefixable: false,
+    },
+    Unit {
+        repr: &["ft", "foot", "feet"],
+        scale: 0.3048,
+        dimension: Dimension::LENGTH,
+        prefixable: false,
+    },
+    Unit {
+        repr: &["yd", "yard", "yards"],
+        scale: 0.9144,
+        dimension: Dimension::LENGTH,
+        prefixable: false,
+    },
+    Unit {
+        repr: &["mi", "mile", "miles"],
+        scale: 1609.344,
+        dimension: Dimension::LENGTH,
+        prefixable: false,
+    },
+    Unit {
+        repr: &["lb", "lbs", "pound", "pounds"],
+        scale: 0.453_592_37,
+        dimension: Dimension::MASS,
+        prefixable: false,
+    },
+    Unit {
+        repr: &["oz", "ounce", "ounces"],
+        scale: 0.028_349_523_125,
+        dimension: Dimension::MASS,
+        prefixable: false,
+    },
+    Unit {
+        repr: &["minute", "minutes"],
+        scale: 60.0,
+        dimension: Dimension::TIME,
+        prefixable: false,
+    },
+    Unit {
+        repr: &["hr", "hour", "hours"],
+        scale: 3600.0,
+        dimension: Dimension::TIME,
+        prefixable: false,
+    },
+    Unit {
+        repr: &["day", "days"],
+        scale: 86400.0,
+        dimension: Dimension::TIME,
+        prefixable: false,
+    },
+    Unit {
+        repr: &["L", "l", "liter", "liters", "litre", "litres"],
+        scale: 1e-3,
+        dimension: Dimension([3, 0, 0, 0, 0, 0, 0]),
+        prefixable: true,
+    },
+    Unit {
+        repr: &["Hz", "hertz"],
+        scale: 1.0,
+        dimension: Dimension([0, 0, -1, 0, 0, 0, 0]),
+        prefixable: true,
+    },
+    Unit {
+        repr: &["N", "newton", "newtons"],
+        scale: 1.0,
+        dimension: Dimension([1, 1, -2, 0, 0, 0, 0]),
+        prefixable: true,
+    },
+    Unit {
+        repr: &["Pa", "pascal", "pascals"],
+        scale: 1.0,
+        dimension: Dimension([-1, 1, -2, 0, 0, 0, 0]),
+        prefixable: true,
+    },
+    Unit {
+        repr: &["atm"],
+        scale: 101_325.0,
+        dimension: Dimension([-1, 1, -2, 0, 0, 0, 0]),
+        prefixable: false,
+    },
+    Unit {
+        repr: &["J", "joule", "joules"],
+        scale: 1.0,
+        dimension: Dimension([2, 1, -2, 0, 0, 0, 0]),
+        prefixable: true,
+    },
+    Unit {
+        repr: &["W", "watt", "watts"],
+        scale: 1.0,
+        dimension: Dimension([2, 1, -3, 0, 0, 0, 0]),
+        prefixable: true,
+    },
+    Unit {
+        repr: &["C", "coulomb", "coulombs"],
+        scale: 1.0,
+        dimension: Dimension([0, 0, 1, 1, 0, 0, 0]),
+        prefixable: true,
+    },
+    Unit {
+        repr: &["V", "volt", "volts"],
+        scale: 1.0,
+        dimension: Dimension([2, 1, -3, -1, 0, 0, 0]),
+        prefixable: true,
+    },
+    Unit {
+        repr: &["Ω", "ohm", "ohms"],
+        scale: 1.0,
+        dimension: Dimension([2, 1, -3, -2, 0, 0, 0]),
+        prefixable: true,
+    },
+];
+
+/// A unit with an optional SI prefix, e.g. `km`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PrefixedUnit {
+    pub prefix: Option<&'static Prefix>,
+    pub unit: &'static Unit,
+}
+
+impl PrefixedUnit {
+    /// Get a unit by one of its representations. Only a unit's symbol accepts a prefix.
+    pub fn by_repr(repr: &str) -> Option<Self> {
+        if let Some(unit) = UNITS.iter().find(|unit| unit.repr.contains(&repr)) {
+            return Some(Self { prefix: None, unit });
+        }
+        PREFIXES.iter().find_map(|prefix| {
+            let rest = prefix.repr.iter().find_map(|p| repr.strip_prefix(p))?;
+            let unit = UNITS
+                .iter()
+                .find(|unit| unit.prefixable && unit.repr[0] == rest)?;
+            Some(Self {
+                prefix: Some(prefix),
+                unit,
+            })
+        })
+    }
+
+    /// Get the unit named by the word at the start of `text`.
+    /// Units are only matched by whole words, so `ms` is milliseconds but `max` isn't a unit.
+    ///
+    /// Returns the unit and the number of characters its name spans
+    pub fn next_unit(text: &str) -> Option<(Self, usize)> {
+        let word: String = text.chars().take_while(|c| c.is_alphabetic()).collect();
+        Self::by_repr(&word).map(|unit| (unit, word.chars().count()))
+    }
+
+    /// Determines if the next sequence is a unit
+    pub fn is(text: &str) -> bool {
+        Self::next_unit(text).is_some()
+    }
+
+    /// The size of the unit in SI base units
+    pub fn scale(&self) -> f64 {
+        self.prefix.map_or(1.0, |prefix| prefix.scale) * self.unit.scale
+    }
+
+    /// The unit's symbol, e.g. `km`
+    pub fn symbol(&self) -> String {
+        format!(
+            "{}{}",
+            self.prefix.map_or("", |prefix| prefix.repr[0]),
+            self.unit.repr[0]
+        )
+    }
+}
+
+/// A product of powers of units, e.g. `kg·m/s^2`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CompoundUnit(Vec<(PrefixedUnit, i32)>);
+
+impl CompoundUnit {
+    /// The size of the unit in SI base units
+    pub fn scale(&self) -> f64 {
+        self.0
+            .iter()
+            .map(|(unit, power)| unit.scale().powi(*power))
+            .product()
+    }
+
+    /// The dimension of the unit, or `None` if an exponent is too large to represent
+    pub fn dimension(&self) -> Option<Dimension> {
+        self.0
+            .iter()
+            .try_fold(Dimension::default(), |dimension, (unit, power)| {
+                dimension.times(unit.unit.dimension.pow(*power)?)
+            })
+    }
+
+    /// The product of two units, combining the powers of repeated units
+    #[must_use]
+    pub fn times(&self, other: &Self) -> Self {
+        let mut out = self.0.clone();
+        for (unit, power) in &other.0 {
+            if let Some(existing) = out.iter_mut().find(|(u, _)| u == unit) {
+                existing.1 += power;
+            } else {
+                out.push((*unit, *power));
+            }
+        }
+        out.retain(|(_, power)| *power != 0);
+        Self(out)
+    }
+
+    /// This unit raised to the power of `n`, or `None` if a power overflows
+    pub fn powi(&self, n: i32) -> Option<Self> {
+        if n == 0 {
+            return Some(Self::default());
+        }
+        self.0
+            .iter()
+            .map(|(unit, power)| Some((*unit, power.checked_mul(n)?)))
+            .collect::<Option<_>>()
+            .map(Self)
+    }
+
+    /// The `n`th root of this unit, or `None` if a power isn't divisible by `n`
+    pub fn root(&self, n: i32) -> Option<Self> {
+        self.0
+            .iter()
+            .map(|(unit, power)| (power % n == 0).then(|| (*unit, power / n)))
+            .collect::<Option<_>>()
+            .map(Self)
+    }
+}
+
+impl fmt::Display for CompoundUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = |(unit, power): &(PrefixedUnit, i32)| {
+            if *power == 1 {
+                unit.symbol()
+            } else {
+                format!("{}^{}", unit.symbol(), power)
+            }
+        };
+        let (numerator, denominator): (Vec<_>, Vec<_>) =
+            self.0.iter().partition(|(_, power)| *power > 0);
+
+        if numerator.is_empty() {
+            return write!(f, "{}", self.0.iter().map(format).join("·"));
+        }
+        write!(f, "{}", numerator.into_iter().map(format).join("·"))?;
+        let denominator: Vec<String> = denominator
+            .into_iter()
+            .map(|(unit, power)| format(&(*unit, -power)))
+            .collect();
+        match denominator.len() {
+            0 => Ok(()),
+            1 => write!(f, "/{}", denominator[0]),
+            _ => write!(f, "/({})", denominator.join("·")),
+        }
+    }
+}
+
+/// A value with a unit, e.g. `5 km`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quantity {
+    /// The value, in terms of `unit`
+    pub value: f64,
+    pub unit: CompoundUnit,
+}
+
+impl Quantity {
+    /// Create a number from a value and unit.
+    /// Dimensionless results, e.g. from `km/m`, become plain numbers.
+    ///
+    /// Returns `None` if the unit's dimension is too large to represent, e.g. `m^200`
+    pub fn number(value: f64, unit: CompoundUnit) -> Option<Number> {
+        Some(if unit.dimension()?.is_dimensionless() {
+            Number::Float(value * unit.scale())
+        } else {
+            Number::Quantity(Box::new(Self { value, unit }))
+        })
+    }
+
+    /// One of the given unit, e.g. `1 km`. No unit is dimensionless.
+    pub fn unit(unit: PrefixedUnit) -> Number {
+        Number::Quantity(Box::new(Self {
+            value: 1.0,
+            unit: CompoundUnit(vec![(unit, 1)]),
+        }))
+    }
+
+    /// The value of this quantity in SI base units
+    pub fn base_value(&self) -> f64 {
+        self.value * self.unit.scale()
+    }
+
+    /// The value of this quantity in terms of another unit of the same dimension
+    fn value_in(&self, unit: &CompoundUnit) -> f64 {
+        self.base_value() / unit.scale()
+    }
+}
+
+/// The value respects the formatter's precision
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.value, f)?;
+        write!(f, " {}", self.unit)
+    }
+}
+
+/// Express every quantity in terms of the unit of the first.
+/// Returns `None` if their dimensions differ.
+fn same_unit(quantities: &[Quantity]) -> Option<Vec<f64>> {
+    let unit = &quantities[0].unit;
+    let dimension = unit.dimension()?;
+    quantities
+        .iter()
+        .map(|quantity| (quantity.unit.dimension()? == dimension).then(|| quantity.value_in(unit)))
+        .collect()
+}
+
+/// Apply an operator to arguments of which at least one has a unit.
+///
+/// ## Errors
+/// Returns an `Error::Dimension` if the dimensions of the arguments are incompatible with each other
+/// or with the operator, e.g. `1 m + 1 s` or `sin(1 m)`, or if the dimension of the result is too large to represent.
+/// Returns an `Error::Domain` for complex arguments and conversions to anything but a unit, e.g. `1 m to 2 cm`
+pub(super) fn evaluate(op: &Operator, args: &[Number]) -> Result<Number, Error> {
+    // The spans are filled in from the operator's token during evaluation
+    let error = || Error::Dimension(op.kind, Span::default());
+    let domain = || Error::Domain(op.kind, Span::default());
+    if args.iter().any(|arg| matches!(arg, Number::Complex(_))) {
+        return Err(domain());
+    }
+
+    let args: Vec<Quantity> = args
+        .iter()
+        .map(|arg| match arg {
+            Number::Quantity(quantity) => (**quantity).clone(),
+            plain => Quantity {
+                value: plain.to_f64(),
+                unit: CompoundUnit::default(),
+            },
+        })
+        .collect();
+    let first = &args[0];
+
+    let result = match op.kind {
+        OperatorType::Mul | OperatorType::UnitMul => {
+            Quantity::number(first.value * args[1].value, first.unit.times(&args[1].unit))
+        }
+        OperatorType::Div => {
+            let inverse = args[1].unit.powi(-1).ok_or_else(error)?;
+            Quantity::number(first.value / args[1].value, first.unit.times(&inverse))
+        }
+        OperatorType::Pow => {
+            let exponent = args[1].value;
+            if !args[1].unit.0.is_empty() || exponent.fract() != 0.0 {
+                return Err(error());
+            }
+            #[allow(clippy::cast_possible_truncation)]
+            let exponent = exponent as i32;
+            let unit = first.unit.powi(exponent).ok_or_else(error)?;
+            Quantity::number(first.value.powi(exponent), unit)
+        }
+        OperatorType::Sqrt | OperatorType::Cbrt => {
+            let n = if op.kind == OperatorType::Sqrt { 2 } else { 3 };
+            let unit = first.unit.root(n).ok_or_else(error)?;
+            Quantity::number((op.doit)(&[first.value]), unit)
+        }
+        OperatorType::Convert => {
+            let target = &args[1];
+            // The target is a unit, like `km/h`, not a quantity like `2 km/h`
+            if target.unit.0.is_empty() {
+                return Err(error());
+            }
+            #[allow(clippy::float_cmp)]
+            if target.value != 1.0 {
+                return Err(domain());
+            }
+            let dimension = first.unit.dimension();
+            if dimension.is_none() || dimension != target.unit.dimension() {
+                return Err(error());
+            }
+            Some(Number::Quantity(Box::new(Quantity {
+                value: first.value_in(&target.unit),
+                unit: target.unit.clone(),
+            })))
+        }
+        OperatorType::Negative
+        | OperatorType::Positive
+        | OperatorType::Abs
+        | OperatorType::Floor
+        | OperatorType::Ceil
+        | OperatorType::Round
+        | OperatorType::Trunc => Quantity::number((op.doit)(&[first.value]), first.unit.clone()),
+        OperatorType::Sign | OperatorType::Not => Some(Number::Float((op.doit)(&[first.value]))),
+        // Comparisons require every argument to have the same dimension and produce plain numbers
+        OperatorType::Less
+        | OperatorType::Greater
+        | OperatorType::LessOrEqual
+        | OperatorType::GreaterOrEqual
+        | OperatorType::Equal
+        | OperatorType::NotEqual => Some(Number::Float((op.doit)(
+            &same_unit(&args).ok_or_else(error)?,
+        ))),
+        // These require every argument to have the same dimension
+        OperatorType::Add
+        | OperatorType::Sub
+        | OperatorType::Mod
+        | OperatorType::Max
+        | OperatorType::Min
+        | OperatorType::Sum
+        | OperatorType::Mean
+        | OperatorType::Median
+        | OperatorType::Hypot => {
+            let values = same_unit(&args).ok_or_else(error)?;
+            Quantity::number((op.doit)(&values), first.unit.clone())
+        }
+        _ => return Err(error()),
+    };
+    // The dimension of the result may be too large to represent
+    result.ok_or_else(error)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{CompoundUnit, Dimension, PrefixedUnit, Quantity};
+    use crate::model::number::Number;
+
+    #[test]
+    fn test_next_unit() {
+        for &(text, symbol, len) in &[
+            ("m/s", "m", 1),
+            ("km", "km", 2),
+            ("kg m", "kg", 2),
+            ("ms", "ms", 2),
+            ("mi", "mi", 2),
+            ("mol", "mol", 3),
+            ("mmol", "mmol", 4),
+            ("cd", "cd", 2),
+            ("μs", "μs", 2),
+            ("us", "μs", 2),
+            ("inches", "in", 6),
+            ("Ω", "Ω", 1),
+        ] {
+            let (unit, n) = PrefixedUnit::next_unit(text).unwrap();
+            assert_eq!((unit.symbol().as_str(), n), (symbol, len), "{}", text);
+        }
+        // Operators, constants, and unprefixable units
+        for text in &["max", "min", "pi", "e", "sin", "h", "kin", "kmeter"] {
+            assert!(!PrefixedUnit::is(text), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_compound_unit() {
+        let unit = |repr| CompoundUnit(vec![(PrefixedUnit::by_repr(repr).unwrap(), 1)]);
+        let (kg, m, s) = (unit("kg"), unit("m"), unit("s"));
+
+        let inverse = |unit: &CompoundUnit| unit.powi(-1).unwrap();
+
+        let newton = kg.times(&m).times(&s.powi(-2).unwrap());
+        assert_eq!(newton.to_string(), "kg·m/s^2");
+        assert_eq!(newton.dimension(), Some(Dimension([1, 1, -2, 0, 0, 0, 0])));
+        assert_same!(newton.scale(), 1.0);
+
+        assert_eq!(inverse(&s).to_string(), "s^-1");
+        assert_eq!(kg.times(&inverse(&m.times(&s))).to_string(), "kg/(m·s)");
+        assert_eq!(m.times(&m).root(2), Some(m.clone()));
+        assert_eq!(m.root(2), None);
+        assert_eq!(m.times(&inverse(&m)), CompoundUnit::default());
+
+        // Exponents that don't fit
+        assert_eq!(m.powi(128).unwrap().dimension(), None);
+        assert_eq!(m.powi(i32::MAX).unwrap().powi(2), None);
+        assert_eq!(unit("L").powi(50).unwrap().dimension(), None);
+    }
+
+    #[test]
+    fn test_dimension() {
+        let length = Dimension([1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(length.pow(127), Some(Dimension([127, 0, 0, 0, 0, 0, 0])));
+        assert_eq!(length.pow(128), None);
+        assert_eq!(length.pow(-128), Some(Dimension([-128, 0, 0, 0, 0, 0, 0])));
+        assert_eq!(Dimension([3, 0, 0, 0, 0, 0, 0]).pow(50), None);
+        assert_eq!(length.pow(100).and_then(|d| d.times(d)), None);
+    }
+
+    #[test]
+    fn test_quantity() {
+        let km = PrefixedUnit::by_repr("km").unwrap();
+        match Quantity::unit(km) {
+            Number::Quantity(q) => {
+                assert_same!(q.base_value(), 1000.0);
+                assert_eq!(format!("{:.1}", q), "1.0 km");
+            }
+            other => panic!("Expected a quantity, got {:?}", other),
+        }
+
+        // Dimensionless units are plain numbers
+        let m = CompoundUnit(vec![(PrefixedUnit::by_repr("m").unwrap(), -1)]);
+        let ratio = CompoundUnit(vec![(km, 1)]).times(&m);
+        assert_eq!(Quantity::number(2.0, ratio), Some(Number::Float(2000.0)));
+    }
+}
@@ -1,30 +1,20 @@
-use super::representable::{get_by_repr, Searchable};
+use super::number::Number;
+use super::symbols::SymbolTable;
 
 #[derive(Clone, Debug, PartialEq)]
 /// Represents a variable, a value with a name
 pub struct Variable {
     pub repr: String,
-    pub value: f64,
-}
-
-impl Searchable for Variable {
-    fn search<'a>(&'a self, search: &str) -> Option<(&'a Self, usize)> {
-        // Case sensitive
-        if search.starts_with(&self.repr) {
-            Some((self, self.repr.chars().count()))
-        } else {
-            None
-        }
-    }
+    pub value: Number,
 }
 
 impl Variable {
-    /// Searches for the first variable in `vars` that matches the representation given by the start of `text`
+    /// Searches for the variable in `vars` with the longest name that matches the start of `text`
     /// * `text` - The string to search. Must start with the name of a variable (not a '$') but can
-    /// be arbitrarily long. Matches are case sensitive.
-    /// * `vars` - A slice of [Variable]s to check for
-    pub fn next_variable<'a>(text: &str, vars: &'a [Self]) -> Option<(&'a Self, usize)> {
-        get_by_repr(text, vars)
+    ///   be arbitrarily long. Matches are case sensitive.
+    /// * `vars` - The [Variable]s to check for
+    pub fn next_variable<'a>(text: &str, vars: &Scope<'a>) -> Option<(&'a Self, usize)> {
+        vars.longest_match(text)
     }
 
     /// Returns whether or not the given representation could reference a valid variable
@@ -33,12 +23,76 @@ impl Variable {
     }
 }
 
+/// The variables visible to an evaluation.
+/// A call to a user function adds a layer with its arguments on top of the caller's scope,
+/// so calls cost the same no matter how many variables are defined.
+#[derive(Clone, Copy)]
+pub struct Scope<'a> {
+    vars: &'a SymbolTable<Variable>,
+    parent: Option<&'a Self>,
+}
+
+impl<'a> Scope<'a> {
+    pub const fn new(vars: &'a SymbolTable<Variable>) -> Self {
+        Self { vars, parent: None }
+    }
+
+    /// A layer of `vars` on top of `parent`. Variables in `vars` shadow those of `parent` with the same name.
+    pub const fn with_parent(vars: &'a SymbolTable<Variable>, parent: &'a Self) -> Self {
+        Self {
+            vars,
+            parent: Some(parent),
+        }
+    }
+
+    /// Get the variable named exactly `name`
+    pub fn get(&self, name: &str) -> Option<&'a Variable> {
+        self.vars
+            .get(name)
+            .or_else(|| self.parent.and_then(|parent| parent.get(name)))
+    }
+
+    /// The generations of the layers' tables, without duplicates.
+    /// Expressions only depend on which names are visible, not on the order of the layers
+    pub fn generations(&self) -> Vec<u64> {
+        let mut generations = vec![self.vars.generation()];
+        let mut layer = self.parent;
+        while let Some(scope) = layer {
+            generations.push(scope.vars.generation());
+            layer = scope.parent;
+        }
+        generations.sort_unstable();
+        generations.dedup();
+        generations
+    }
+
+    /// Find the variable with the longest name that `text` starts with, see [`SymbolTable::longest_match`]
+    pub fn longest_match(&self, text: &str) -> Option<(&'a Variable, usize)> {
+        let inner = self.vars.longest_match(text);
+        let outer = self.parent.and_then(|parent| parent.longest_match(text));
+        match (inner, outer) {
+            (Some((_, inner_len)), Some((var, outer_len))) if outer_len > inner_len => {
+                Some((var, outer_len))
+            }
+            (None, outer) => outer,
+            (inner, _) => inner,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
     #![allow(clippy::non_ascii_literal)]
 
-    use super::Variable;
+    use super::{super::symbols::SymbolTable, Scope, Variable};
+
+    fn var(repr: &str, value: f64) -> Variable {
+        Variable {
+            repr: repr.to_string(),
+            value: value.into(),
+        }
+    }
 
     #[test]
     fn test_is() {
@@ -51,23 +105,48 @@ mod tests {
     #[allow(clippy::shadow_unrelated)]
     #[test]
     fn test_next_variable() {
-        let vars = [
+        let vars = SymbolTable::from([
             Variable {
                 repr: "abc".to_string(),
-                value: 1.0,
+                value: 1.0.into(),
             },
             Variable {
                 repr: "😂❤😂".to_string(),
-                value: 5.5,
+                value: 5.5.into(),
             },
-        ];
-        let search = Variable::next_variable("abc", &vars).unwrap();
+        ]);
+        let scope = Scope::new(&vars);
+        let search = Variable::next_variable("abc", &scope).unwrap();
         assert_eq!(*search.0, vars[0]);
         assert_eq!(search.1, 3);
-        let search = Variable::next_variable("qqq", &vars);
+        let search = Variable::next_variable("qqq", &scope);
         assert!(search.is_none());
-        let search = Variable::next_variable("😂❤😂", &vars).unwrap();
+        let search = Variable::next_variable("😂❤😂", &scope).unwrap();
         assert_eq!(*search.0, vars[1]);
         assert_eq!(search.1, 3);
     }
+
+    #[test]
+    fn test_scope() {
+        let outer = SymbolTable::from([var("x", 1.0), var("xx", 2.0), var("y", 3.0)]);
+        let inner = SymbolTable::from([var("x", 4.0), var("z", 5.0)]);
+        let outer = Scope::new(&outer);
+        let scope = Scope::with_parent(&inner, &outer);
+
+        // Inner variables shadow outer ones
+        assert_eq!(scope.get("x"), Some(&var("x", 4.0)));
+        assert_eq!(scope.get("y"), Some(&var("y", 3.0)));
+        assert_eq!(scope.get("z"), Some(&var("z", 5.0)));
+        assert_eq!(outer.get("z"), None);
+
+        // The longest name wins, whichever layer it's in
+        assert_eq!(scope.longest_match("xx"), Some((&var("xx", 2.0), 2)));
+        assert_eq!(scope.longest_match("xy"), Some((&var("x", 4.0), 1)));
+        assert_eq!(scope.longest_match("q"), None);
+
+        // Repeated layers, as in recursive calls, don't add generations
+        let twice = Scope::with_parent(&inner, &scope);
+        assert_eq!(twice.generations(), scope.generations());
+        assert_eq!(scope.generations().len(), 2);
+    }
 }
@@ -1,6 +1,7 @@
 use super::model::{
     errors::Error,
     operators::Associativity,
+    span::{Span, Spanned},
     tokens::{ParenType, Token},
 };
 
@@ -9,25 +10,65 @@ use super::model::{
 ///
 /// Returns a `Vec` of token in RPN or an `Error::MismatchingParens`. This function will catch
 /// some instances of parentheses-mismatch, but not all.
-pub fn rpn<'a>(tokens: &'a [Token]) -> Result<Vec<Token<'a>>, Error> {
-    let mut operator_stack: Vec<Token> = Vec::new();
-    let mut output: Vec<Token> = Vec::with_capacity(tokens.len());
+///
+/// The output contains no parentheses or commas, except that every call to a functional-style operator
+/// or user function is preceded by a left paren, as a marker of where its arguments begin.
+/// e.g. `max(1, 2)` => `( 1 2 max`. Empty arguments, e.g. `max(1,,2)`, are an `Error::Parsing` at the comma.
+pub fn rpn<'a>(tokens: &[Spanned<Token<'a>>]) -> Result<Vec<Spanned<Token<'a>>>, Error> {
+    let mut operator_stack: Vec<Spanned<Token>> = Vec::new();
+    let mut output: Vec<Spanned<Token>> = Vec::with_capacity(tokens.len());
 
-    for token in tokens {
-        match token {
-            Token::Comma => {}
-            Token::Number { .. } | Token::Constant { .. } | Token::Variable { .. } => {
-                output.push(*token);
+    for (idx, token) in tokens.iter().enumerate() {
+        if let Some(span) = empty_argument(idx.checked_sub(1).map(|prev| &tokens[prev]), token) {
+            return Err(Error::Parsing(span));
+        }
+        match &token.inner {
+            Token::Comma => {
+                // Finish the current argument
+                while let Some(last) = operator_stack.last() {
+                    if matches!(
+                        last.inner,
+                        Token::Paren {
+                            kind: ParenType::Left
+                        }
+                    ) {
+                        break;
+                    }
+                    output.push(operator_stack.pop().unwrap());
+                }
+            }
+            Token::Number { .. }
+            | Token::Constant { .. }
+            | Token::Unit { .. }
+            | Token::Variable { .. } => {
+                output.push(token.clone());
             }
             Token::Operator { inner: op1 } => {
-                while !operator_stack.is_empty() {
+                // Calls without parentheses are marked too, e.g. `hypot 3`
+                let called = matches!(
+                    tokens.get(idx + 1).map(|next| &next.inner),
+                    Some(Token::Paren {
+                        kind: ParenType::Left
+                    })
+                );
+                if op1.is_functional() && !called {
+                    output.push(Spanned::new(
+                        Token::Paren {
+                            kind: ParenType::Left,
+                        },
+                        token.span,
+                    ));
+                }
+
+                // A prefix operator has no left operand, so it can't complete anything on the stack
+                while !op1.is_prefix() && !operator_stack.is_empty() {
                     let last = operator_stack.last().unwrap();
-                    if let Token::Paren { kind } = last {
+                    if let Token::Paren { kind } = &last.inner {
                         if *kind == ParenType::Left {
                             break;
                         }
                     }
-                    if let Token::Operator { inner: op2 } = last {
+                    if let Token::Operator { inner: op2 } = &last.inner {
                         if !(op2.precedence() > op1.precedence()
                             || (op2.precedence() == op1.precedence()
                                 && op1.associativity() == Associativity::Left))
@@ -37,24 +78,33 @@ pub fn rpn<'a>(tokens: &'a [Token]) -> Result<Vec<Token<'a>>, Error> {
                     }
                     output.push(operator_stack.pop().unwrap());
                 }
-                operator_stack.push(*token);
+                operator_stack.push(token.clone());
             }
             Token::Paren { kind } => match kind {
-                ParenType::Left => operator_stack.push(*token),
+                ParenType::Left => {
+                    if matches!(
+                        operator_stack.last().map(|last| &last.inner),
+                        Some(Token::Operator { inner }) if inner.is_functional()
+                    ) {
+                        output.push(token.clone());
+                    }
+                    operator_stack.push(token.clone());
+                }
                 ParenType::Right => {
                     loop {
-                        if operator_stack.is_empty() {
-                            return Err(Error::MismatchingParens);
-                        }
-                        let op = operator_stack.pop().unwrap();
-                        if let Token::Paren { kind } = op {
+                        let Some(op) = operator_stack.pop() else {
+                            return Err(Error::MismatchingParens(token.span));
+                        };
+                        if let Token::Paren { kind } = op.inner {
                             if kind == ParenType::Left {
                                 break;
                             }
                         }
                         output.push(op);
                     }
-                    if matches!(operator_stack.last(), Some(Token::Operator { .. })) {
+                    // These were the parentheses of a function call, so the function comes next
+                    if matches!(operator_stack.last().map(|last| &last.inner), Some(Token::Operator { inner }) if inner.is_functional())
+                    {
                         output.push(operator_stack.pop().unwrap());
                     }
                 }
@@ -63,35 +113,176 @@ pub fn rpn<'a>(tokens: &'a [Token]) -> Result<Vec<Token<'a>>, Error> {
     }
 
     // Pop all of `operator_stack` onto `output`
-    output.extend(operator_stack.iter().rev());
+    output.extend(operator_stack.into_iter().rev());
 
     Ok(output)
 }
 
+/// Find the comma that ends or follows an empty argument, e.g. the second comma of `max(1,,2)`
+fn empty_argument(previous: Option<&Spanned<Token>>, token: &Spanned<Token>) -> Option<Span> {
+    let previous = previous?;
+    match (&previous.inner, &token.inner) {
+        (
+            Token::Comma
+            | Token::Paren {
+                kind: ParenType::Left,
+            },
+            Token::Comma,
+        ) => Some(token.span),
+        (
+            Token::Comma,
+            Token::Paren {
+                kind: ParenType::Right,
+            },
+        ) => Some(previous.span),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
-    use super::{rpn, Error, ParenType, Token};
+    use super::{rpn, Error, ParenType, Span, Spanned, Token};
     use crate::model::operators::OperatorType;
 
     #[test]
     fn test_rpn() {
         let tokens = [
-            Token::Number { value: 1.0 },
+            Token::Number { value: 1.0.into() },
             Token::operator(OperatorType::Add),
-            Token::Number { value: 3.0 },
+            Token::Number { value: 3.0.into() },
         ];
-        let tokens = rpn(&tokens).unwrap();
+        let tokens = rpn(&tokens.map(Spanned::from)).unwrap();
         assert_eq!(
             tokens,
             [
-                Token::Number { value: 1.0 },
-                Token::Number { value: 3.0 },
+                Token::Number { value: 1.0.into() },
+                Token::Number { value: 3.0.into() },
                 Token::operator(OperatorType::Add)
             ]
         );
     }
 
+    #[test]
+    fn test_rpn_variadic() {
+        // max(1 + 2, 3)
+        let tokens = [
+            Token::operator(OperatorType::Max),
+            Token::Paren {
+                kind: ParenType::Left,
+            },
+            Token::Number { value: 1.0.into() },
+            Token::operator(OperatorType::Add),
+            Token::Number { value: 2.0.into() },
+            Token::Comma,
+            Token::Number { value: 3.0.into() },
+            Token::Paren {
+                kind: ParenType::Right,
+            },
+        ];
+        let tokens = rpn(&tokens.map(Spanned::from)).unwrap();
+        assert_eq!(
+            tokens,
+            [
+                Token::Paren {
+                    kind: ParenType::Left
+                },
+                Token::Number { value: 1.0.into() },
+                Token::Number { value: 2.0.into() },
+                Token::operator(OperatorType::Add),
+                Token::Number { value: 3.0.into() },
+                Token::operator(OperatorType::Max),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rpn_calls() {
+        // hypot(3, 4) + sin 2
+        let tokens = [
+            Token::operator(OperatorType::Hypot),
+            Token::Paren {
+                kind: ParenType::Left,
+            },
+            Token::Number { value: 3.0.into() },
+            Token::Comma,
+            Token::Number { value: 4.0.into() },
+            Token::Paren {
+                kind: ParenType::Right,
+            },
+            Token::operator(OperatorType::Add),
+            Token::operator(OperatorType::Sin),
+            Token::Number { value: 2.0.into() },
+        ];
+        let tokens = rpn(&tokens.map(Spanned::from)).unwrap();
+        assert_eq!(
+            tokens,
+            [
+                Token::Paren {
+                    kind: ParenType::Left
+                },
+                Token::Number { value: 3.0.into() },
+                Token::Number { value: 4.0.into() },
+                Token::operator(OperatorType::Hypot),
+                Token::Paren {
+                    kind: ParenType::Left
+                },
+                Token::Number { value: 2.0.into() },
+                Token::operator(OperatorType::Sin),
+                Token::operator(OperatorType::Add),
+            ]
+        );
+
+        // max(1,,2)
+        let tokens = [
+            Token::operator(OperatorType::Max),
+            Token::Paren {
+                kind: ParenType::Left,
+            },
+            Token::Number { value: 1.0.into() },
+            Token::Comma,
+            Token::Comma,
+            Token::Number { value: 2.0.into() },
+            Token::Paren {
+                kind: ParenType::Right,
+            },
+        ];
+        let tokens: Vec<Spanned<Token>> = IntoIterator::into_iter(tokens)
+            .enumerate()
+            .map(|(idx, token)| Spanned::new(token, Span::new(idx, idx + 1)))
+            .collect();
+        assert_eq!(rpn(&tokens), Err(Error::Parsing(Span::new(4, 5))));
+    }
+
+    #[test]
+    fn test_rpn_parens_before_operator() {
+        // 2 * (3) ^ 2
+        let tokens = [
+            Token::Number { value: 2.0.into() },
+            Token::operator(OperatorType::Mul),
+            Token::Paren {
+                kind: ParenType::Left,
+            },
+            Token::Number { value: 3.0.into() },
+            Token::Paren {
+                kind: ParenType::Right,
+            },
+            Token::operator(OperatorType::Pow),
+            Token::Number { value: 2.0.into() },
+        ];
+        let tokens = rpn(&tokens.map(Spanned::from)).unwrap();
+        assert_eq!(
+            tokens,
+            [
+                Token::Number { value: 2.0.into() },
+                Token::Number { value: 3.0.into() },
+                Token::Number { value: 2.0.into() },
+                Token::operator(OperatorType::Pow),
+                Token::operator(OperatorType::Mul),
+            ]
+        );
+    }
+
     #[test]
     fn test_rpn_mismatched_parens() {
         let tokens = [
@@ -105,7 +296,7 @@ mod tests {
                 kind: ParenType::Right,
             },
         ];
-        let result = rpn(&tokens);
-        assert!(matches!(result, Err(Error::MismatchingParens)));
+        let result = rpn(&tokens.map(Spanned::from));
+        assert_eq!(result, Err(Error::MismatchingParens(Span::default())));
     }
 }
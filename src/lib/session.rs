@@ -0,0 +1,517 @@
+use std::rc::Rc;
+
+use crate::{
+    doeval,
+    model::{
+        errors::{ContextualError, Error, ErrorContext},
+        functions::{Function, Functions},
+        number::Number,
+        span::{Span, Spanned},
+        symbols::SymbolTable,
+        tokens::Token,
+        variables::{Scope, Variable},
+        EvaluationContext, IdentifierMode, Options,
+    },
+    tokenize,
+    tokenize::{identifier_len, is_identifier_start},
+    tokenize_and_transform,
+    utils::split_assignment,
+};
+
+/// The name of the variable that holds the result of the last evaluated expression
+pub const ANS: &str = "ans";
+
+/// An evaluation environment that owns its variables and functions.
+/// Statements executed in a session may define variables and functions that later statements can use.
+///
+/// ## Examples
+/// ```
+/// # use rustmatheval::{Outcome, Session};
+/// let mut session = Session::default();
+/// session.execute("$r = 2").unwrap();
+/// session.execute("#area $r = pi * $r^2").unwrap();
+/// if let Outcome::Value(area) = session.execute("#area($r)").unwrap() {
+///     assert_eq!(format!("{:.2}", area), "12.57");
+/// }
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Session {
+    vars: SymbolTable<Variable>,
+    funcs: SymbolTable<Function>,
+    pub options: Options,
+}
+
+/// The result of executing a statement in a [`Session`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// An expression was evaluated, its value is also stored in `$ans`
+    Value(Number),
+    /// A variable was assigned or reassigned
+    Assignment(Variable),
+    /// A function was defined or redefined
+    Definition(Function),
+}
+
+impl Session {
+    pub fn new(options: Options) -> Self {
+        Self {
+            options,
+            ..Self::default()
+        }
+    }
+
+    /// The variables in this session, in the order they were first assigned
+    pub const fn vars(&self) -> &SymbolTable<Variable> {
+        &self.vars
+    }
+
+    /// The functions in this session, in the order they were first defined
+    pub const fn funcs(&self) -> &SymbolTable<Function> {
+        &self.funcs
+    }
+
+    /// Get the value of the variable named `name`, without its `$` prefix
+    pub fn var(&self, name: &str) -> Option<&Number> {
+        self.vars.get(name).map(|var| &var.value)
+    }
+
+    /// Get the function named `name`, without its `#` prefix
+    pub fn func(&self, name: &str) -> Option<&Function> {
+        self.funcs.get(name)
+    }
+
+    /// Assign or reassign a variable
+    pub fn set_var(&mut self, var: Variable) {
+        self.vars.insert(var);
+    }
+
+    /// Define or redefine a function
+    pub fn set_func(&mut self, func: Function) {
+        self.funcs.insert(func);
+    }
+
+    /// Remove the variable named `name`, returning it if it existed
+    pub fn remove_var(&mut self, name: &str) -> Option<Variable> {
+        self.vars.remove(name)
+    }
+
+    /// Remove the function named `name`, returning it if it existed
+    pub fn remove_func(&mut self, name: &str) -> Option<Function> {
+        self.funcs.remove(name)
+    }
+
+    /// Remove every variable, including `$ans`
+    pub fn clear_vars(&mut self) {
+        self.vars = SymbolTable::new();
+    }
+
+    /// Remove every function
+    pub fn clear_funcs(&mut self) {
+        self.funcs = SymbolTable::new();
+    }
+
+    /// The context for evaluating expressions against this session's variables and functions.
+    /// Each context has a fresh budget, so the session's limits apply to each evaluation separately.
+    pub fn context(&self) -> EvaluationContext<'_> {
+        EvaluationContext {
+            vars: Scope::new(&self.vars),
+            funcs: &self.funcs,
+            depth: 0,
+            context: ErrorContext::Main,
+            options: self.options,
+            budget: Rc::default(),
+        }
+    }
+
+    /// Tokenize an expression against this session's variables and functions
+    ///
+    /// ## Errors
+    /// Reraises errors that occur during tokenization
+    pub fn tokenize(&self, expr: &str) -> Result<Vec<Spanned<Token<'_>>>, Error> {
+        tokenize_and_transform(expr, &self.context())
+    }
+
+    /// The code of `func` with its bare identifiers written with their sigils, e.g. `#double($x)` for `double(x)`.
+    /// Sigils are read in either identifier mode, so the result can be executed in both.
+    /// The code is returned unchanged if it doesn't tokenize against this session
+    pub fn sigil_code(&self, func: &Function) -> String {
+        if self.options.identifiers != IdentifierMode::Bare {
+            return func.code.clone();
+        }
+
+        // The arguments' values don't matter, only their names
+        let args = func.create_variables(&vec![Number::Float(0.0); func.arity()]);
+        let vars = Scope::new(&self.vars);
+        let context = EvaluationContext {
+            vars: Scope::with_parent(&args, &vars),
+            ..self.context()
+        };
+        let Ok(tokens) = tokenize(&func.code, &context) else {
+            return func.code.clone();
+        };
+
+        // Spans count characters
+        let chars: Vec<char> = func.code.chars().collect();
+        let mut code = String::new();
+        let mut last = 0;
+        for token in tokens {
+            let sigil = match token.inner {
+                Token::Variable { .. } => '$',
+                Token::Operator {
+                    inner: Functions::User(_),
+                } => '#',
+                _ => continue,
+            };
+            let start = token.span.start;
+            if chars[start] != sigil {
+                code.extend(&chars[last..start]);
+                code.push(sigil);
+                last = start;
+            }
+        }
+        code.extend(&chars[last..]);
+        code
+    }
+
+    /// Evaluate an expression and store the result in `$ans`
+    ///
+    /// ## Errors
+    /// Returns an error if the expression couldn't be computed
+    pub fn eval(&mut self, expr: &str) -> Result<Number, ContextualError> {
+        let (value, _) = doeval(expr, self.context())?;
+        self.set_var(Variable {
+            repr: ANS.to_string(),
+            value: value.clone(),
+        });
+        Ok(value)
+    }
+
+    /// Execute a statement: a variable assignment, `$x = 1`, a function definition, `#f $a = $a + 1`,
+    /// or otherwise an expression to evaluate.
+    /// With [`IdentifierMode::Bare`], `x = 1` and `f(a) = $a + 1` are also accepted.
+    ///
+    /// ## Errors
+    /// Returns [`Error::Assignment`] for malformed assignments, or any error from evaluating an expression.
+    /// The spans of errors are relative to the whole statement.
+    pub fn execute(&mut self, input: &str) -> Result<Outcome, ContextualError> {
+        let Some((left, right)) = split_assignment(input) else {
+            return self.eval(input).map(Outcome::Value);
+        };
+
+        // The length of the statement before the expression, including the `=`
+        let offset = left.chars().count() + 1;
+
+        // Multiple assignments in one statement, or nothing to assign
+        if split_assignment(right).is_some() || right.trim().is_empty() {
+            let span = Span::new(offset - 1, offset + right.chars().count());
+            return Err(Error::Assignment(span).with_context(ErrorContext::Main));
+        }
+
+        let left = left.trim();
+        let bare = self.options.identifiers == IdentifierMode::Bare;
+        if let Some(name) = left.strip_prefix('$') {
+            // Sigiled names may contain anything but whitespace
+            let valid = !(name.is_empty() || name.contains(char::is_whitespace));
+            self.assign_var(valid, name, right, offset)
+                .map(Outcome::Assignment)
+        } else if let Some(left) = left.strip_prefix('#') {
+            let mut split = left.split_whitespace();
+            let name = split.next().unwrap_or_default();
+            let args: Option<Vec<&str>> = split.map(|arg| arg.strip_prefix('$')).collect();
+            let valid = !name.is_empty()
+                && args
+                    .as_ref()
+                    .is_some_and(|args| args.iter().all(|arg| !arg.is_empty()));
+            self.assign_func(valid, name, args.unwrap_or_default(), right, offset)
+                .map(Outcome::Definition)
+        } else if bare && is_identifier(left) {
+            self.assign_var(true, left, right, offset)
+                .map(Outcome::Assignment)
+        } else if let (true, Some((name, args))) = (bare, left.split_once('(')) {
+            let (name, args) = (name.trim(), args.strip_suffix(')').unwrap_or("?"));
+            let args: Vec<&str> = if args.trim().is_empty() {
+                vec![]
+            } else {
+                args.split(',').map(str::trim).collect()
+            };
+            let valid = is_identifier(name) && args.iter().all(|arg| is_identifier(arg));
+            self.assign_func(valid, name, args, right, offset)
+                .map(Outcome::Definition)
+        } else {
+            let span = Span::new(0, offset - 1);
+            Err(Error::Assignment(span).with_context(ErrorContext::Main))
+        }
+    }
+
+    /// * `valid` - Whether `repr` is a valid name
+    /// * `offset` - The length of the statement before `right`, including the `=`
+    fn assign_var(
+        &mut self,
+        valid: bool,
+        repr: &str,
+        right: &str,
+        offset: usize,
+    ) -> Result<Variable, ContextualError> {
+        if !valid {
+            let span = Span::new(0, offset - 1);
+            return Err(Error::Assignment(span).with_context(ErrorContext::Main));
+        }
+
+        // Errors in the expression are relative to the start of the statement
+        let (value, _) = doeval(right, self.context()).map_err(|error| error.offset(offset))?;
+
+        let var = Variable {
+            repr: repr.to_string(),
+            value,
+        };
+        self.set_var(var.clone());
+        Ok(var)
+    }
+
+    /// * `valid` - Whether `name` and `args` are valid names
+    /// * `offset` - The length of the statement before `right`, including the `=`
+    fn assign_func(
+        &mut self,
+        valid: bool,
+        name: &str,
+        args: Vec<&str>,
+        right: &str,
+        offset: usize,
+    ) -> Result<Function, ContextualError> {
+        if !valid {
+            let span = Span::new(0, offset - 1);
+            return Err(Error::Assignment(span).with_context(ErrorContext::Main));
+        }
+
+        let args = args.into_iter().map(str::to_string).collect();
+        let func = Function::new(name.to_string(), args, right.trim().to_string());
+        self.set_func(func.clone());
+        Ok(func)
+    }
+}
+
+/// Determines if `name` is a valid name for a variable or function
+fn is_identifier(name: &str) -> bool {
+    name.chars().next().is_some_and(is_identifier_start)
+        && identifier_len(name) == name.chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+
+    #![allow(clippy::shadow_unrelated)]
+
+    use super::{Outcome, Session};
+    use crate::model::{
+        errors::{Error, ErrorContext},
+        functions::Function,
+        number::Number,
+        span::Span,
+        variables::Variable,
+        IdentifierMode, Options,
+    };
+    use num_bigint::BigInt;
+
+    fn int(n: i64) -> Number {
+        BigInt::from(n).into()
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut session = Session::default();
+
+        let outcome = session.execute("$x = 2 + 3").unwrap();
+        assert_eq!(
+            outcome,
+            Outcome::Assignment(Variable {
+                repr: "x".to_string(),
+                value: int(5),
+            })
+        );
+
+        match session.execute("#sq $a = $a * $a").unwrap() {
+            Outcome::Definition(func) => {
+                assert_eq!(func.name, "sq");
+                assert_eq!(func.args, ["a"]);
+                assert_eq!(func.code, "$a * $a");
+            }
+            other => panic!("Expected a definition, got {:?}", other),
+        }
+
+        let outcome = session.execute("#sq($x) + 1").unwrap();
+        assert_eq!(outcome, Outcome::Value(int(26)));
+        assert_eq!(session.var("ans"), Some(&int(26)));
+
+        let outcome = session.execute("$ans == 26").unwrap();
+        assert_eq!(outcome, Outcome::Value(int(1)));
+
+        // Reassignment replaces the existing variable
+        session.execute("$x = $x - 1").unwrap();
+        assert_eq!(session.var("x"), Some(&int(4)));
+        assert_eq!(session.vars().len(), 2);
+        assert!(session.func("sq").is_some());
+    }
+
+    #[test]
+    fn test_longest_match() {
+        let mut session = Session::default();
+        for statement in &["$a = 1", "$abc = 2", "$ab = 3"] {
+            session.execute(statement).unwrap();
+        }
+
+        let names: Vec<&str> = session.vars().iter().map(|v| v.repr.as_str()).collect();
+        assert_eq!(names, ["a", "abc", "ab"]);
+
+        // `$abc` isn't read as `$a` followed by `bc`
+        let result = session.eval("$abc + $a + $ab").unwrap();
+        assert_eq!(result, int(6));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut session = Session::default();
+        for statement in &["$a = 1", "$b = 2", "#f $x = $x + $b", "#f($a)"] {
+            session.execute(statement).unwrap();
+        }
+
+        assert!(session.remove_var("b").is_some());
+        assert!(session.remove_var("b").is_none());
+        // `$b` no longer exists, so `#f` can't be evaluated
+        let error = session.eval("#f($a)").unwrap_err();
+        assert_eq!(error.error, Error::UnknownVariable(Span::new(5, 7)));
+
+        session.clear_vars();
+        assert!(session.var("a").is_none() && session.var("ans").is_none());
+        assert!(session.remove_func("f").is_some());
+        assert!(session.funcs().is_empty());
+    }
+
+    #[test]
+    fn test_function_recompiled() {
+        let mut session = Session::default();
+        for statement in &["$b = 2", "#g $a = $a * $bm", "#h $bm = #g(1)"] {
+            session.execute(statement).unwrap();
+        }
+        assert_eq!(session.eval("#g(1)").unwrap().to_string(), "2 m");
+
+        // `$bm` now refers to the new variable rather than `$b` in metres
+        session.execute("$bm = 5").unwrap();
+        assert_eq!(session.eval("#g(1)").unwrap(), int(5));
+        session.remove_var("bm");
+        assert_eq!(session.eval("#g(1)").unwrap().to_string(), "2 m");
+
+        // The arguments of callers are visible too
+        assert_eq!(session.eval("#h(7)").unwrap(), int(7));
+        assert_eq!(session.eval("#g(1)").unwrap().to_string(), "2 m");
+    }
+
+    #[test]
+    fn test_execute_bare() {
+        let mut session = Session::new(Options {
+            identifiers: IdentifierMode::Bare,
+            ..Options::default()
+        });
+
+        session.execute("r = 3").unwrap();
+        session.execute("hyp(a, b) = sqrt(a^2 + b^2)").unwrap();
+        session.execute("zero() = 0").unwrap();
+        session.execute("#g $a = $a + 1").unwrap();
+        assert_eq!(session.func("hyp").unwrap().args, ["a", "b"]);
+
+        let outcome = session.execute("hyp(r, 4) + g(zero())").unwrap();
+        assert_eq!(outcome, Outcome::Value(6.0.into()));
+
+        for statement in &[
+            "2r = 1",
+            "f(a b) = 1",
+            "f(a, 2) = 1",
+            "f(a = 1",
+            "$r $s = 1",
+        ] {
+            let error = session.execute(statement).unwrap_err();
+            assert!(matches!(error.error, Error::Assignment(_)), "{}", statement);
+        }
+    }
+
+    #[test]
+    fn test_sigil_code() {
+        let bare = Options {
+            identifiers: IdentifierMode::Bare,
+            ..Options::default()
+        };
+        let mut session = Session::new(bare);
+        session.execute("r = 3").unwrap();
+        session.execute("hyp(a, b) = sqrt(a^2 + b^2)").unwrap();
+        session.execute("f(x) = hyp(x, r) * pi + $x").unwrap();
+        let f = session.func("f").unwrap();
+        assert_eq!(session.sigil_code(f), "#hyp($x, $r) * pi + $x");
+
+        // The rewritten code means the same in either identifier mode
+        for &options in &[Options::default(), bare] {
+            let mut restored = Session::new(options);
+            restored.execute("$r = 3").unwrap();
+            for func in session.funcs().iter() {
+                let args: Vec<String> = func.args.iter().map(|arg| format!("${}", arg)).collect();
+                let code = session.sigil_code(func);
+                restored
+                    .execute(&format!("#{} {} = {}", func.name, args.join(" "), code))
+                    .unwrap();
+            }
+            assert_eq!(
+                restored.execute("#f(4)").unwrap(),
+                session.execute("#f(4)").unwrap()
+            );
+        }
+
+        // Outside of bare mode, code is written as it was defined
+        let session = Session::default();
+        let g = Function::new("g".to_string(), vec![], "x + 1".to_string());
+        assert_eq!(session.sigil_code(&g), "x + 1");
+    }
+
+    #[test]
+    fn test_execute_err() {
+        let mut session = Session::default();
+
+        for statement in &["$x =", "$x = $y = 1", "1 = 2", "$ = 1", "#f a = 1", "# = 1"] {
+            let error = session.execute(statement).unwrap_err();
+            assert!(matches!(error.error, Error::Assignment(_)), "{}", statement);
+        }
+
+        // Errors point into the whole statement
+        let error = session.execute("$x = 1 + $y").unwrap_err();
+        assert_eq!(error.error, Error::UnknownVariable(Span::new(9, 11)));
+        assert_eq!(error.context, ErrorContext::Main);
+
+        assert!(session.vars().is_empty());
+        assert!(session.funcs().is_empty());
+    }
+
+    #[test]
+    fn test_execute_trace() {
+        let mut session = Session::default();
+        session.execute("#c $x = $x + $q").unwrap();
+        session.execute("#b $x = 1 + #c($x * 2)").unwrap();
+        session.execute("#a $x = #b($x)").unwrap();
+
+        let error = session.execute("$y = #a(3)").unwrap_err();
+        assert_eq!(error.error, Error::UnknownVariable(Span::new(5, 7)));
+        assert!(matches!(error.context, ErrorContext::Scoped(func) if func.name == "c"));
+
+        // Innermost first, the outermost call is relative to the whole statement
+        let calls: Vec<(&str, &[Number], Span)> = error
+            .trace
+            .iter()
+            .map(|frame| (frame.name.as_str(), frame.args.as_slice(), frame.span))
+            .collect();
+        assert_eq!(
+            calls,
+            [
+                ("c", &[int(6)][..], Span::new(4, 6)),
+                ("b", &[int(3)][..], Span::new(0, 2)),
+                ("a", &[int(3)][..], Span::new(5, 7)),
+            ]
+        );
+        assert_eq!(error.trace[2].to_string(), "#a(3) at index 5");
+    }
+}
@@ -1,6 +1,6 @@
 use crate::model::{
     functions::{Function, Functions},
-    EvaluationContext,
+    EvaluationContext, IdentifierMode,
 };
 
 use super::{
@@ -8,8 +8,10 @@ use super::{
         constants::Constant,
         errors::Error,
         operators::{Operator, OperatorType},
+        span::{Span, Spanned},
         tokens::ParenType,
         tokens::Token,
+        units::PrefixedUnit,
         variables::Variable,
     },
     utils::{self, Pos},
@@ -22,13 +24,17 @@ enum TokenType {
     Function,
     Paren,
     Constant,
+    Unit,
     Variable,
     Comma,
 }
 
-fn _type(s: &str) -> Option<TokenType> {
+fn token_type(s: &str) -> Option<TokenType> {
+    // Units are whole words, so they take precedence over operators that start with the same letters
     Some(if Token::is_next_number(s) {
         TokenType::Number
+    } else if PrefixedUnit::is(s) {
+        TokenType::Unit
     } else if Operator::is(s) {
         TokenType::Operator
     } else if Token::is_next_paren(s) {
@@ -46,22 +52,124 @@ fn _type(s: &str) -> Option<TokenType> {
     })
 }
 
+/// Determines if `c` can begin a bare identifier
+pub fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+/// The length, in characters, of the bare identifier at the start of `text`
+pub fn identifier_len(text: &str) -> usize {
+    text.chars()
+        .enumerate()
+        .take_while(|(n, c)| is_identifier_start(*c) || (*n > 0 && c.is_alphanumeric()))
+        .count()
+}
+
+/// Takes the value found by a prefix search only if it spans all of `word`
+fn whole<T>(word: &str, found: Option<(T, usize)>) -> Option<T> {
+    found
+        .filter(|(_, len)| *len == word.chars().count())
+        .map(|(value, _)| value)
+}
+
+/// The length, in characters, of the word at the start of `text`
+fn word_len(text: &str) -> usize {
+    text.chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '.')
+        .count()
+}
+
+/// The span of the word starting at `idx`, which is at least one character long.
+/// Used to report errors about the token at `idx`.
+/// * `text` - The input starting at `idx`
+fn word_span(idx: usize, text: &str) -> Span {
+    Span::new(idx, idx + word_len(text).max(1))
+}
+
+/// The span of the sigil at `idx` and the name that follows it, e.g. `$foo`
+/// * `text` - The input starting at `idx`
+fn sigil_span(idx: usize, text: &str) -> Span {
+    Span::new(idx, idx + 1 + word_len(&text[1..]))
+}
+
+/// Resolve a bare identifier, see [`IdentifierMode::Bare`]
+/// * `word` - The identifier
+/// * `rest` - The input following the identifier
+/// * `span` - The span of the identifier, for errors
+///
+/// Returns the token and whether an operator following it would be unary
+#[allow(clippy::option_if_let_else)]
+fn identifier<'a>(
+    word: &str,
+    rest: &str,
+    span: Span,
+    context: &EvaluationContext<'a>,
+) -> Result<(Token<'a>, bool), Error> {
+    match (context.vars.get(word), context.funcs.get(word)) {
+        (Some(_), Some(_)) => return Err(Error::Ambiguous(span)),
+        (Some(var), None) => return Ok((Token::Variable { inner: var }, false)),
+        (None, Some(func)) => {
+            let token = Token::Operator {
+                inner: Functions::User(func),
+            };
+            return Ok((token, func.arity() > 0));
+        }
+        (None, None) => {}
+    }
+
+    if let Some(constant) = whole(word, Constant::by_repr(word)) {
+        Ok((Token::Constant { inner: constant }, false))
+    } else if let Some(operator) = whole(word, Operator::by_repr(word)) {
+        let token = Token::Operator {
+            inner: Functions::Builtin(operator),
+        };
+        Ok((token, operator.kind != OperatorType::Factorial))
+    } else if let Some(unit) = PrefixedUnit::by_repr(word) {
+        Ok((Token::Unit { inner: unit }, false))
+    } else if rest.trim_start().starts_with('(') {
+        Err(Error::UnknownFunction(span))
+    } else {
+        Err(Error::UnknownVariable(span))
+    }
+}
+
 /// Tokenize an input string
 /// * `string` - A string containing a mathematical expression
 /// * `vars` - The available `Variable`s
 ///
-/// Returns a list of tokens or an error
-#[allow(
-    clippy::unnecessary_unwrap,
-    clippy::too_many_lines,
-    clippy::missing_errors_doc
-)]
+/// Returns a list of tokens, each with the span of the input it was parsed from, or an error
+#[allow(clippy::missing_errors_doc)]
 pub fn tokenize<'a>(
     string: &str,
     context: &EvaluationContext<'a>,
-) -> Result<Vec<Token<'a>>, Error> {
-    let mut tokens: Vec<Token> = Vec::new();
-    let mut explicit_paren = 0;
+) -> Result<Vec<Spanned<Token<'a>>>, Error> {
+    let mut tokens = Vec::new();
+    tokenize_into(string, context, &mut tokens)?;
+    Ok(tokens)
+}
+
+/// Tokenize as much of a string as possible, e.g. to highlight input that is still being written.
+///
+/// Returns the tokens before the first error, along with the error if there was one.
+/// A left paren that was never closed is reported after every token.
+pub fn tokenize_partial<'a>(
+    string: &str,
+    context: &EvaluationContext<'a>,
+) -> (Vec<Spanned<Token<'a>>>, Option<Error>) {
+    let mut tokens = Vec::new();
+    let error = tokenize_into(string, context, &mut tokens).err();
+    (tokens, error)
+}
+
+/// Tokenize `string`, pushing each token onto `tokens` as it's parsed
+#[allow(clippy::unnecessary_unwrap, clippy::too_many_lines)]
+fn tokenize_into<'a>(
+    string: &str,
+    context: &EvaluationContext<'a>,
+    tokens: &mut Vec<Spanned<Token<'a>>>,
+) -> Result<(), Error> {
+    // The spans of the left parens that haven't been closed yet
+    let mut open_parens: Vec<Span> = Vec::new();
 
     // Indicates that the current operator would be unary
     let mut unary = true;
@@ -80,7 +188,25 @@ pub fn tokenize<'a>(
             continue;
         }
 
-        let kind: TokenType = _type(&slice).ok_or(Error::Parsing(idx))?;
+        // Bare identifiers are whole words
+        if context.options.identifiers == IdentifierMode::Bare && is_identifier_start(c) {
+            let len = identifier_len(&slice);
+            let (word, rest) = slice.split_at(
+                slice
+                    .char_indices()
+                    .nth(len)
+                    .map_or(slice.len(), |(n, _)| n),
+            );
+            let span = Span::new(idx, idx + len);
+            let (token, unary_) = identifier(word, rest, span, context)?;
+            idx += len;
+            tokens.push(Spanned::new(token, span));
+            unary = unary_;
+            continue;
+        }
+
+        let kind: TokenType =
+            token_type(&slice).ok_or_else(|| Error::Parsing(word_span(idx, &slice)))?;
 
         let (token, len, unary_) = match kind {
             TokenType::Operator => {
@@ -105,7 +231,7 @@ pub fn tokenize<'a>(
             }
             TokenType::Function => {
                 let (func, len) = Function::next_function(&slice[1..], context.funcs)
-                    .ok_or(Error::UnknownFunction(idx))?;
+                    .ok_or_else(|| Error::UnknownFunction(sigil_span(idx, &slice)))?;
                 let token = Token::Operator {
                     inner: Functions::User(func),
                 };
@@ -113,15 +239,22 @@ pub fn tokenize<'a>(
             }
             TokenType::Paren => {
                 let (token, kind) = Token::paren(c).unwrap();
-                let (paren_mod, unary_) = match kind {
-                    ParenType::Left => (1, true),
-                    ParenType::Right => (-1, false),
+                let span = Span::new(idx, idx + 1);
+                let unary_ = match kind {
+                    ParenType::Left => {
+                        open_parens.push(span);
+                        true
+                    }
+                    ParenType::Right => {
+                        open_parens.pop().ok_or(Error::MismatchingParens(span))?;
+                        false
+                    }
                 };
-                explicit_paren += paren_mod;
                 (token, 1, unary_)
             }
             TokenType::Number => {
-                let (token, len) = Token::number(&slice).ok_or(Error::Parsing(idx))?;
+                let (token, len) =
+                    Token::number(&slice).ok_or_else(|| Error::Parsing(word_span(idx, &slice)))?;
                 (token, len, false)
             }
             TokenType::Constant => {
@@ -129,10 +262,14 @@ pub fn tokenize<'a>(
                 let token = Token::Constant { inner: constant };
                 (token, len, false)
             }
+            TokenType::Unit => {
+                let (unit, len) = PrefixedUnit::next_unit(&slice).unwrap();
+                (Token::Unit { inner: unit }, len, false)
+            }
             TokenType::Variable => {
                 // [1..] to ignore the $ prefix
-                let (variable, len) = Variable::next_variable(&slice[1..], context.vars)
-                    .ok_or(Error::UnknownVariable(idx))?;
+                let (variable, len) = Variable::next_variable(&slice[1..], &context.vars)
+                    .ok_or_else(|| Error::UnknownVariable(sigil_span(idx, &slice)))?;
                 let token = Token::Variable { inner: variable };
                 // len + 1 to account for '$'
                 (token, len + 1, false)
@@ -140,24 +277,31 @@ pub fn tokenize<'a>(
             TokenType::Comma => (Token::Comma, 1, true),
         };
 
+        tokens.push(Spanned::new(token, Span::new(idx, idx + len)));
         idx += len;
-        tokens.push(token);
         unary = unary_;
     }
-    if explicit_paren == 0 {
-        Ok(tokens)
-    } else {
-        Err(Error::MismatchingParens)
+    // A left paren that was never closed
+    if let Some(span) = open_parens.pop() {
+        return Err(Error::MismatchingParens(span));
     }
+
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
 
-    use crate::model::errors::ErrorContext;
+    use crate::model::{
+        constants::ConstantType, errors::ErrorContext, number::Literal, span::Span,
+        symbols::SymbolTable, variables::Scope,
+    };
 
     use super::OperatorType::{Add, Factorial};
-    use super::{tokenize, Error, EvaluationContext, OperatorType, ParenType, Token, Variable};
+    use super::{
+        tokenize, tokenize_partial, Error, EvaluationContext, OperatorType, ParenType, Token,
+        Variable,
+    };
 
     #[test]
     fn test_tokenize_simple_ok() {
@@ -165,9 +309,9 @@ mod tests {
         assert_eq!(
             tokens.unwrap(),
             [
-                Token::Number { value: 1.0 },
+                Token::Number { value: 1.0.into() },
                 Token::operator(Add),
-                Token::Number { value: 1.0 }
+                Token::Number { value: 1.0.into() }
             ]
         );
 
@@ -178,9 +322,9 @@ mod tests {
                 Token::Paren {
                     kind: ParenType::Left
                 },
-                Token::Number { value: 1.0 },
+                Token::Number { value: 1.0.into() },
                 Token::operator(Add),
-                Token::Number { value: 1.0 },
+                Token::Number { value: 1.0.into() },
                 Token::Paren {
                     kind: ParenType::Right
                 },
@@ -191,26 +335,67 @@ mod tests {
         assert_eq!(
             tokens.unwrap(),
             [
-                Token::Number { value: 1.0 },
+                Token::Number { value: 1.0.into() },
                 Token::operator(Factorial),
                 Token::operator(Add),
-                Token::Number { value: 1.0 },
+                Token::Number { value: 1.0.into() },
             ]
         );
 
-        let tokens = tokenize("sin 5 exp 2 + cos 5^2", &EvaluationContext::default());
+        let tokens = tokenize("sin 5 pow 2 + cos 5^2", &EvaluationContext::default());
         assert_eq!(
             tokens.unwrap(),
             [
                 Token::operator(OperatorType::Sin),
-                Token::Number { value: 5.0 },
+                Token::Number { value: 5.0.into() },
                 Token::operator(OperatorType::Pow),
-                Token::Number { value: 2.0 },
+                Token::Number { value: 2.0.into() },
                 Token::operator(OperatorType::Add),
                 Token::operator(OperatorType::Cos),
-                Token::Number { value: 5.0 },
+                Token::Number { value: 5.0.into() },
                 Token::operator(OperatorType::Pow),
-                Token::Number { value: 2.0 }
+                Token::Number { value: 2.0.into() }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_numbers() {
+        let context = EvaluationContext::default();
+
+        let tokens = tokenize("6.022e23 + 0x1F", &context).unwrap();
+        assert_eq!(
+            tokens,
+            [
+                Token::Number {
+                    value: Literal::parse("6.022e23").unwrap()
+                },
+                Token::operator(Add),
+                Token::Number { value: 31.0.into() },
+            ]
+        );
+
+        // Without digits after it, `e` is still Euler's number
+        let tokens = tokenize("2e", &context).unwrap();
+        assert_eq!(
+            tokens,
+            [
+                Token::Number { value: 2.0.into() },
+                Token::constant(ConstantType::E),
+            ]
+        );
+
+        let tokens = tokenize("1_000 - 1e-3", &context).unwrap();
+        assert_eq!(
+            tokens,
+            [
+                Token::Number {
+                    value: 1000.0.into()
+                },
+                Token::operator(OperatorType::Sub),
+                Token::Number {
+                    value: Literal::parse("1e-3").unwrap()
+                },
             ]
         );
     }
@@ -237,17 +422,11 @@ mod tests {
     fn test_tokenize_mismatched_parens() {
         let context = EvaluationContext::default();
 
+        // The unmatched paren is highlighted
         let result = tokenize("((1)) + (1))", &context);
-        match result {
-            Err(Error::MismatchingParens) => {}
-            _ => panic!("Expected mismatched parens"),
-        }
-
+        assert_eq!(result, Err(Error::MismatchingParens(Span::new(11, 12))));
         let result = tokenize("(()", &context);
-        match result {
-            Err(Error::MismatchingParens) => {}
-            _ => panic!("Expected mismatched parens"),
-        }
+        assert_eq!(result, Err(Error::MismatchingParens(Span::new(0, 1))));
     }
 
     #[test]
@@ -255,57 +434,105 @@ mod tests {
         let context = EvaluationContext::default();
 
         let result = tokenize("1 + 2 + h", &context);
-        assert!(matches!(result, Err(Error::Parsing(8))));
+        assert_eq!(result, Err(Error::Parsing(Span::new(8, 9))));
         let result = tokenize("1 + 2eq + 6", &context);
-        assert!(matches!(result, Err(Error::Parsing(6))));
+        assert_eq!(result, Err(Error::Parsing(Span::new(6, 7))));
+    }
+
+    #[test]
+    fn test_tokenize_partial() {
+        let context = EvaluationContext::default();
+
+        let (tokens, error) = tokenize_partial("1 + h + 2", &context);
+        assert_eq!(
+            tokens,
+            [Token::Number { value: 1.0.into() }, Token::operator(Add)]
+        );
+        assert_eq!(error, Some(Error::Parsing(Span::new(4, 5))));
+
+        // Unclosed parens are only known to be unclosed at the end
+        let (tokens, error) = tokenize_partial("(1 + (2)", &context);
+        assert_eq!(tokens.len(), 6);
+        assert_eq!(error, Some(Error::MismatchingParens(Span::new(0, 1))));
+
+        let (tokens, error) = tokenize_partial("3!", &context);
+        assert_eq!(
+            tokens,
+            [
+                Token::Number { value: 3.0.into() },
+                Token::operator(Factorial)
+            ]
+        );
+        assert_eq!(error, None);
+    }
+
+    #[test]
+    fn test_tokenize_spans() {
+        let vars = SymbolTable::from([Variable {
+            repr: "xx".to_string(),
+            value: 1.0.into(),
+        }]);
+        let context = EvaluationContext {
+            vars: Scope::new(&vars),
+            ..EvaluationContext::default()
+        };
+        let tokens = tokenize("sin $xx pow 5.5", &context).unwrap();
+        let spans: Vec<Span> = tokens.iter().map(|token| token.span).collect();
+        assert_eq!(
+            spans,
+            [
+                Span::new(0, 3),
+                Span::new(4, 7),
+                Span::new(8, 11),
+                Span::new(12, 15)
+            ]
+        );
     }
 
     #[test]
     fn test_tokenize_unknown_variable() {
-        let vars = [Variable {
+        let vars = SymbolTable::from([Variable {
             repr: "q".to_string(),
-            value: 1.0,
-        }];
+            value: 1.0.into(),
+        }]);
         let context = EvaluationContext {
-            vars: &vars,
-            funcs: &[],
+            vars: Scope::new(&vars),
             depth: 0,
             context: ErrorContext::Main,
+            ..EvaluationContext::default()
         };
         let result = tokenize("$x", &context);
-        assert!(matches!(result, Err(Error::UnknownVariable(0))));
+        assert_eq!(result, Err(Error::UnknownVariable(Span::new(0, 2))));
         let result = tokenize("1 * $x", &context);
-        assert!(matches!(result, Err(Error::UnknownVariable(4))));
+        assert_eq!(result, Err(Error::UnknownVariable(Span::new(4, 6))));
     }
 
     #[test]
     fn test_tokenize_variables_ok() {
-        // It's important that these variables are sorted by length in descending order
-        let vars = [
+        // `$xx` is matched over `$x` regardless of the order of these variables
+        let vars = SymbolTable::from([
             Variable {
                 repr: "xx".to_string(),
-                value: 10.0,
+                value: 10.0.into(),
             },
             Variable {
                 repr: "x".to_string(),
-                value: 3.0,
+                value: 3.0.into(),
             },
-        ];
+        ]);
         let context = EvaluationContext {
-            vars: &vars,
-            funcs: &[],
+            vars: Scope::new(&vars),
             depth: 0,
             context: ErrorContext::Main,
+            ..EvaluationContext::default()
         };
         let tokens = tokenize("1 + $x", &context);
         assert_eq!(
             tokens.unwrap(),
             [
-                Token::Number { value: 1.0 },
+                Token::Number { value: 1.0.into() },
                 Token::operator(OperatorType::Add),
-                Token::Variable {
-                    inner: &context.vars[1]
-                }
+                Token::Variable { inner: &vars[1] }
             ]
         );
 
@@ -314,17 +541,15 @@ mod tests {
             tokens.unwrap(),
             [
                 Token::operator(OperatorType::Sin),
-                Token::Variable {
-                    inner: &context.vars[0]
-                },
+                Token::Variable { inner: &vars[0] },
                 Token::operator(OperatorType::Pow),
-                Token::Number { value: 5.0 },
+                Token::Number { value: 5.0.into() },
                 Token::operator(OperatorType::Add),
                 Token::operator(OperatorType::Cos),
                 Token::Paren {
                     kind: ParenType::Left
                 },
-                Token::Number { value: 6.54 },
+                Token::Number { value: 6.54.into() },
                 Token::Paren {
                     kind: ParenType::Right
                 }
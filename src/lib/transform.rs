@@ -1,22 +1,63 @@
 use crate::model::{
     functions::Functions,
-    operators::{OperatorType, FUNCTIONAL_STYLE_OPERATORS},
+    operators::{Arity, OperatorType, FUNCTIONAL_STYLE_OPERATORS},
+    span::{Span, Spanned},
     tokens::{ParenType, Token},
 };
 
+/// Functional-style builtin operators *that only take a single argument*
+const IMPLICIT_PAREN_OPERATORS: &[OperatorType] = &[
+    OperatorType::Sin,
+    OperatorType::Cos,
+    OperatorType::Tan,
+    OperatorType::Sqrt,
+    OperatorType::Cbrt,
+    OperatorType::Ln,
+    OperatorType::Log2,
+    OperatorType::Log10,
+    OperatorType::Exp,
+    OperatorType::Asin,
+    OperatorType::Acos,
+    OperatorType::Atan,
+    OperatorType::Sinh,
+    OperatorType::Cosh,
+    OperatorType::Tanh,
+    OperatorType::Abs,
+    OperatorType::Floor,
+    OperatorType::Ceil,
+    OperatorType::Round,
+    OperatorType::Trunc,
+    OperatorType::Sign,
+    OperatorType::Re,
+    OperatorType::Im,
+    OperatorType::Arg,
+    OperatorType::Conj,
+    OperatorType::Gamma,
+    OperatorType::Lgamma,
+    OperatorType::Erf,
+    OperatorType::Erfc,
+    OperatorType::IsPrime,
+    OperatorType::NextPrime,
+    OperatorType::Factor,
+];
+
 /// Insert implicit parantheses into the tokens.
 /// Implicit parentheses are inserted for arguments to functions
 /// or function-like operators that accept 0 or 1 arguments
 /// Ex: sin sin 2^5 + 9 => sin(sin(2^5)) + 9
-pub fn implicit_parens(tokens: &mut Vec<Token>) {
+///
+/// Inserted parentheses have an empty span just after the token they follow
+pub fn implicit_parens(tokens: &mut Vec<Spanned<Token>>) {
     let mut implicit_paren: usize = 0;
 
     let mut idx = 0;
     while idx < tokens.len() {
-        let (cur, next) = {
-            let mut iter = tokens.iter();
-            (iter.nth(idx).unwrap(), iter.next())
-        };
+        let (cur, next) = (
+            tokens[idx].inner.clone(),
+            tokens.get(idx + 1).map(|next| next.inner.clone()),
+        );
+        let span = Span::at(tokens[idx].span.end);
+        let inserted = |kind| Spanned::new(Token::Paren { kind }, span);
 
         let preclude = matches!(
             next,
@@ -39,46 +80,33 @@ pub fn implicit_parens(tokens: &mut Vec<Token>) {
         // The delay case should _never_ coincide with the `else if` condition on this block, so it's ok
         if matches!(
             cur,
-            Token::Number { .. } | Token::Variable { .. } | Token::Constant { .. }
+            Token::Number { .. }
+                | Token::Variable { .. }
+                | Token::Constant { .. }
+                | Token::Unit { .. }
         ) && !delay
         {
+            let paren = inserted(ParenType::Right);
             for offset in 0..implicit_paren {
-                tokens.insert(
-                    idx + 1 + offset as usize,
-                    Token::Paren {
-                        kind: ParenType::Right,
-                    },
-                );
+                tokens.insert(idx + 1 + offset, paren.clone());
             }
-            idx += implicit_paren as usize;
+            idx += implicit_paren;
             implicit_paren = 0;
         } else if !preclude {
             let wants_implicit_paren = match cur {
                 Token::Operator { inner } => match inner {
-                    // Functional-style builtin operators *that only take a single argument*
-                    Functions::Builtin(op) => [
-                        OperatorType::Sin,
-                        OperatorType::Cos,
-                        OperatorType::Tan,
-                        OperatorType::Sqrt,
-                    ]
-                    .contains(&op.kind),
+                    // Variadic operators wrap a single argument, e.g. `max 5` => `max(5)`
+                    Functions::Builtin(op) => {
+                        IMPLICIT_PAREN_OPERATORS.contains(&op.kind) || op.arity == Arity::Variadic
+                    }
                     Functions::User(func) => {
                         if func.arity() == 1 {
                             true
                         } else {
-                            tokens.insert(
-                                idx + 1,
-                                Token::Paren {
-                                    kind: ParenType::Left,
-                                },
-                            );
-                            tokens.insert(
-                                idx + 2,
-                                Token::Paren {
-                                    kind: ParenType::Right,
-                                },
-                            );
+                            let (left, right) =
+                                (inserted(ParenType::Left), inserted(ParenType::Right));
+                            tokens.insert(idx + 1, left);
+                            tokens.insert(idx + 2, right);
                             idx += 2;
                             false
                         }
@@ -87,12 +115,8 @@ pub fn implicit_parens(tokens: &mut Vec<Token>) {
                 _ => false,
             };
             if wants_implicit_paren {
-                tokens.insert(
-                    idx + 1,
-                    Token::Paren {
-                        kind: ParenType::Left,
-                    },
-                );
+                let paren = inserted(ParenType::Left);
+                tokens.insert(idx + 1, paren);
                 implicit_paren += 1;
                 idx += 1;
             }
@@ -104,14 +128,16 @@ pub fn implicit_parens(tokens: &mut Vec<Token>) {
 /// Insert implicit coefficients into the tokens.
 /// It's important that parantheses and commas are present.
 /// `tokens` should be run through `implicit_parantheses` before this function
+///
+/// Inserted operators have an empty span just after the token they follow
 #[allow(clippy::unnested_or_patterns)]
-pub fn implicit_coeffs(tokens: &mut Vec<Token>) {
+pub fn implicit_coeffs(tokens: &mut Vec<Spanned<Token>>) {
     let mut idx = 0;
     while idx < tokens.len() {
-        let (cur, next) = {
-            let mut iter = tokens.iter();
-            (iter.nth(idx).unwrap(), iter.next())
-        };
+        let (cur, next) = (
+            tokens[idx].inner.clone(),
+            tokens.get(idx + 1).map(|next| next.inner.clone()),
+        );
 
         // Certain tokens preclude coefficients
         // Cases, the next token:
@@ -139,6 +165,7 @@ pub fn implicit_coeffs(tokens: &mut Vec<Token>) {
                 } => op.kind == OperatorType::Factorial,
                 Token::Number { .. }
                 | Token::Constant { .. }
+                | Token::Unit { .. }
                 | Token::Variable { .. }
                 | Token::Paren {
                     kind: ParenType::Right,
@@ -147,7 +174,14 @@ pub fn implicit_coeffs(tokens: &mut Vec<Token>) {
             };
 
             if can_coeff {
-                tokens.insert(idx + 1, Token::operator(OperatorType::Mul));
+                // Units bind tightly to the value before them, e.g. `5 m / 2 s` => `5·m / 2·s`
+                let kind = if matches!(next, Some(Token::Unit { .. })) {
+                    OperatorType::UnitMul
+                } else {
+                    OperatorType::Mul
+                };
+                let span = Span::at(tokens[idx].span.end);
+                tokens.insert(idx + 1, Spanned::new(Token::operator(kind), span));
                 idx += 1;
             }
         }
@@ -163,23 +197,28 @@ mod tests {
 
     use crate::{
         model::{
-            errors::ErrorContext, functions::Function, operators::OperatorType, tokens::Token,
-            variables::Variable, EvaluationContext,
+            errors::ErrorContext,
+            functions::Function,
+            operators::OperatorType,
+            symbols::SymbolTable,
+            tokens::Token,
+            variables::{Scope, Variable},
+            EvaluationContext,
         },
         tokenize,
     };
 
     #[test]
     fn test_coeff() {
-        let vars = [Variable {
+        let vars = SymbolTable::from([Variable {
             repr: "q".to_string(),
-            value: 1.0,
-        }];
+            value: 1.0.into(),
+        }]);
         let context = EvaluationContext {
-            vars: &vars,
-            funcs: &[],
+            vars: Scope::new(&vars),
             depth: 0,
             context: ErrorContext::Main,
+            ..EvaluationContext::default()
         };
 
         let mul = Token::operator(OperatorType::Mul);
@@ -195,6 +234,13 @@ mod tests {
         assert_eq!(tokens[1], mul);
         assert_eq!(tokens[3], mul);
         assert_eq!(tokens[8], mul);
+
+        let mut tokens = tokenize("5 m / 2 s", &context).unwrap();
+        implicit_coeffs(&mut tokens);
+
+        let unit_mul = Token::operator(OperatorType::UnitMul);
+        assert_eq!(tokens[1], unit_mul);
+        assert_eq!(tokens[5], unit_mul);
     }
 
     #[test]
@@ -202,11 +248,11 @@ mod tests {
         let mut tokens = tokenize("sin 5 cos 5", &EvaluationContext::default()).unwrap();
         implicit_parens(&mut tokens);
 
-        let funcs = [Function {
-            name: "ident".to_string(),
-            args: vec!["a".to_string()],
-            code: "$a".to_string(),
-        }];
+        let funcs = SymbolTable::from([Function::new(
+            "ident".to_string(),
+            vec!["a".to_string()],
+            "$a".to_string(),
+        )]);
         let context = EvaluationContext {
             funcs: &funcs,
             ..EvaluationContext::default()
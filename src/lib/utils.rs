@@ -5,7 +5,6 @@
     clippy::cast_possible_truncation,
     clippy::cast_sign_loss
 )]
-
 pub enum Pos {
     Idx(usize),
     End,
@@ -52,6 +51,31 @@ pub fn slice(string: &str, start: usize, end: &Pos) -> String {
     string.chars().skip(start).take(end).collect()
 }
 
+/// Splits an assignment statement into its left and right sides, at the first `=`
+/// that isn't part of a comparison operator: `==`, `!=`, `<=`, or `>=`
+///
+/// Returns `None` if `input` isn't an assignment
+///
+/// ## Examples
+/// ```
+/// # use rustmatheval::utils::split_assignment;
+/// assert_eq!(split_assignment("$x = 1"), Some(("$x ", " 1")));
+/// assert_eq!(split_assignment("1 <= 2"), None);
+/// ```
+#[must_use]
+pub fn split_assignment(input: &str) -> Option<(&str, &str)> {
+    let bytes = input.as_bytes();
+    (0..bytes.len())
+        .find(|idx| {
+            let prev = idx.checked_sub(1).map(|prev| bytes[prev]);
+            let next = bytes.get(idx + 1);
+            bytes[*idx] == b'='
+                && !matches!(prev, Some(b'=' | b'!' | b'<' | b'>'))
+                && next != Some(&b'=')
+        })
+        .map(|idx| (&input[..idx], &input[idx + 1..]))
+}
+
 #[macro_export]
 macro_rules! same {
     ($a:expr, $b:expr) => {
@@ -72,7 +96,7 @@ macro_rules! assert_same {
 #[cfg(test)]
 mod tests {
 
-    #![allow(unused_must_use)]
+    #![allow(unused_must_use, clippy::should_panic_without_expect)]
 
     use super::{slice, Pos};
 